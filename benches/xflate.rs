@@ -0,0 +1,41 @@
+//! Compares XFlate with its backend on its own, and with gzip, on a
+//! directory of XML documents.
+//!
+//! Runs over `tests/data` by default, or the directories and files
+//! given in `XFLATE_BENCH_PATHS`, separated like `PATH`:
+//!
+//! ```sh
+//! XFLATE_BENCH_PATHS=corpus/ cargo bench
+//! ```
+
+#[path = "../src/bin/xflate/bench.rs"]
+mod bench;
+#[path = "../src/bin/xflate/peak.rs"]
+mod peak;
+
+use std::{env, path::PathBuf, process::ExitCode};
+
+use xflate::XFlateConfig;
+
+const ITERATIONS: usize = 20;
+
+fn main() -> ExitCode {
+    let paths: Vec<PathBuf> = match env::var_os("XFLATE_BENCH_PATHS") {
+        Some(paths) => env::split_paths(&paths).collect(),
+        None => vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data")],
+    };
+
+    let report = bench::load(&paths)
+        .and_then(|samples| bench::run(&samples, &XFlateConfig::default(), true, ITERATIONS));
+
+    match report {
+        Ok(report) => {
+            print!("{}", report);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
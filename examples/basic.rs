@@ -1,6 +1,3 @@
-use std::io::Read;
-
-use itertools::Itertools;
 use xflate::{self, XmlBCompressor, XmlNSymbolTable};
 
 static BASIC_XML: &str = r#"<section xml:lang="en">
@@ -32,10 +29,10 @@ fn main() {
     let mut tag_table = xflate::XmlNDynamicTagTable::new();
 
     println!("===== xflate Compression =====");
-    println!("");
+    println!();
 
     println!("XML: {}", xml);
-    println!("");
+    println!();
 
     let xmln = match xflate::encode_xmln(file, &mut sym_table, &mut tag_table) {
         Ok(xmln) => xmln,
@@ -44,7 +41,7 @@ fn main() {
         }
     };
     println!("XMLN: {}", xmln);
-    println!("");
+    println!();
 
     let xmls = match xflate::encode_xmls(xmln.as_str()) {
         Ok(xmls) => xmls,
@@ -53,7 +50,7 @@ fn main() {
         }
     };
     println!("XMLS: {:?}", xmls);
-    println!("");
+    println!();
 
     let backend = xflate::XmlBDeflateBackend::new(xflate::XmlBCompress::Fast);
     let xmlb = match backend.compress(&xmls) {
@@ -63,21 +60,21 @@ fn main() {
         }
     };
     println!("XMLB: {:?}", xmlb);
-    println!("");
+    println!();
 
     println!("=== xflate Compression End ===");
-    println!("");
+    println!();
 
     println!("{}", sym_table);
-    println!("");
+    println!();
     println!("{}", tag_table);
-    println!("");
+    println!();
 
     println!("===  xflate Decompression  ===");
-    println!("");
+    println!();
 
     println!("XMLB: {:?}", xmlb);
-    println!("");
+    println!();
 
     let xmls = match xflate::decode_xmlb(&xmlb, &backend) {
         Ok(xmls) => xmls,
@@ -86,7 +83,7 @@ fn main() {
         }
     };
     println!("XMLS: {:?}", xmls);
-    println!("");
+    println!();
 
     let xmln = match xflate::decode_xmls(&xmls) {
         Ok(xmln) => xmln,
@@ -95,25 +92,29 @@ fn main() {
         }
     };
     println!("XMLN: {}", xmln);
-    println!("");
+    println!();
 
     let xml = xflate::decode_xmln(xmln.as_str(), &mut sym_table, &mut tag_table);
     if let Err(err) = xml {
         panic!("Error decoding XMLN: {:?}", err);
     }
     println!("XML: {}", xml.unwrap());
-    println!("");
+    println!();
 
     println!("=== xflate Decompression End ===");
-    println!("");
+    println!();
 
     println!("=======     Results     =======");
-    println!("XML Bytes: {:?}", file.bytes().count());
-    println!("XMLN Bytes: {}", xmln.bytes().count());
-    println!("XMLS Bytes: {:?}", xmls.bytes().count());
-    println!("XMLB Bytes: {:?}", xmlb.bytes().count());
-    println!(
-        "Compression Ratio (compress / orig): {:.2}%",
-        (xmlb.bytes().count() as f64 / file.bytes().count() as f64) * 100.0
-    );
+    let config = xflate::XFlateConfig {
+        symbol_size: SYMBOL_SIZE,
+        xmlb_opt: xflate::XmlBCompress::Fast,
+        ..xflate::XFlateConfig::default()
+    };
+    let (_, stats) = match xflate::XFlate::new(config).compress_with_stats(file) {
+        Ok(result) => result,
+        Err(err) => {
+            panic!("Error compressing XML: {}", err);
+        }
+    };
+    println!("{}", stats);
 }
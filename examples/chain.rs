@@ -0,0 +1,51 @@
+use std::fs;
+
+use xflate::{
+    XmlBChain, XmlBCodec, XmlBCompress, XmlBDeflateBackend, XmlBMoveToFront, XmlBRunLength,
+    XmlNDynamicSymbolTable, XmlNDynamicTagTable, XmlNSymbolTable, decode_xmlb, encode_xmlb,
+    encode_xmln, encode_xmls,
+};
+
+fn main() {
+    let xml = fs::read_to_string("tests/data/basic.xml").expect("Failed to open file");
+
+    let mut sym_table = XmlNDynamicSymbolTable::new(2);
+    let mut tag_table = XmlNDynamicTagTable::new();
+
+    let xmln =
+        encode_xmln(xml.as_bytes(), &mut sym_table, &mut tag_table).expect("Failed to encode XMLN");
+    let xmls = encode_xmls(&xmln).expect("Failed to encode XMLS");
+
+    let pipelines: Vec<(&str, Box<dyn XmlBCodec>)> = vec![
+        (
+            "deflate",
+            Box::new(XmlBDeflateBackend::new(XmlBCompress::Best)),
+        ),
+        (
+            "rle + deflate",
+            Box::new(
+                XmlBChain::new()
+                    .then(XmlBRunLength)
+                    .then(XmlBDeflateBackend::new(XmlBCompress::Best)),
+            ),
+        ),
+        (
+            "mtf + rle + deflate",
+            Box::new(
+                XmlBChain::new()
+                    .then(XmlBMoveToFront)
+                    .then(XmlBRunLength)
+                    .then(XmlBDeflateBackend::new(XmlBCompress::Best)),
+            ),
+        ),
+    ];
+
+    println!("XMLS Bytes: {}", xmls.len());
+    for (name, backend) in pipelines {
+        let xmlb = encode_xmlb(&xmls, backend.as_ref()).expect("Failed to compress XMLS");
+        let decoded = decode_xmlb(&xmlb, backend.as_ref()).expect("Failed to decompress XMLB");
+        assert_eq!(decoded, xmls);
+
+        println!("{}: {} bytes", name, xmlb.len());
+    }
+}
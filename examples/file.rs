@@ -1,7 +1,4 @@
-use std::{
-    fs,
-    io::{Cursor, Read},
-};
+use std::{fs, io::Cursor};
 
 use xflate::{XFlate, XFlateConfig};
 
@@ -13,21 +10,23 @@ fn main() {
     let config = XFlateConfig::from_xml(cursor1).expect("Failed to create XFlateConfig");
     let mut xflate = XFlate::new(config);
 
-    let compressed = xflate.compress(cursor2).expect("Failed to compress XML");
+    let (compressed, stats) = xflate
+        .compress_with_stats(cursor2)
+        .expect("Failed to compress XML");
 
-    println!("Bytes original: {}", xml.bytes().len());
-    println!("Bytes compressed: {:?}", compressed.bytes().count());
+    println!("{}", stats);
+    println!();
 
     let decompressed = xflate
         .decompress(Cursor::new(compressed))
         .expect("Failed to decompress XML");
     println!("Bytes decompressed: {}", decompressed.len());
 
-    println!("");
+    println!();
     println!("----- Original document -----");
     println!("{}", xml);
 
-    println!("");
+    println!();
     println!("----- Decompressed document -----");
     println!("{}", decompressed);
 }
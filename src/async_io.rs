@@ -0,0 +1,367 @@
+use std::{
+    io::{self, Read},
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+use crate::{XFlate, XFlateConfig, XFlateError, limits::check_input};
+
+/// Bytes read from the inner reader, and passed on as output, at a time
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Blocks of output the pipeline may run ahead of the adapter
+const OUTPUT_BLOCKS: usize = 4;
+
+/// Pipeline run over the data by the adapters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pipeline {
+    Compress,
+    Decompress,
+}
+
+impl Pipeline {
+    /// Most input passed to the pipeline. XML larger than the output
+    /// limit couldn't be decompressed with the same config, and
+    /// compressed data larger than any frame `decompress` accepts
+    /// would be rejected once it had all been read.
+    fn max_input_bytes(self, config: &XFlateConfig) -> usize {
+        match self {
+            Pipeline::Compress => config.limits.max_output_bytes,
+            Pipeline::Decompress => config.limits.max_frame_bytes(),
+        }
+    }
+
+    /// Run the pipeline over `input`, passing the output to `sink`
+    /// in blocks
+    fn run<F>(
+        self,
+        config: XFlateConfig,
+        input: InputReader,
+        mut sink: F,
+    ) -> Result<(), XFlateError>
+    where
+        F: FnMut(&[u8]) -> Result<(), XFlateError>,
+    {
+        let mut xflate = XFlate::new(config);
+
+        match self {
+            Pipeline::Compress => {
+                let compressed = xflate.compress(input)?;
+                compressed.chunks(CHUNK_SIZE).try_for_each(sink)
+            }
+            Pipeline::Decompress => xflate.decompress_to(input, |xml| sink(xml.as_bytes())),
+        }
+    }
+}
+
+/// Input of a running pipeline, read on the blocking pool as
+/// the adapter passes it on
+struct InputReader {
+    chunks: mpsc::UnboundedReceiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for InputReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => (self.chunk, self.pos) = (chunk, 0),
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+
+        Ok(len)
+    }
+}
+
+/// Pipeline running on the blocking pool, so the runtime isn't held
+/// up while it runs. Input is passed to it as it comes, and output
+/// is passed back in blocks, no more than `OUTPUT_BLOCKS` ahead of
+/// the adapter.
+struct Task {
+    /// Taken once the input ends
+    input: Option<mpsc::UnboundedSender<Vec<u8>>>,
+    /// Bytes of input passed on so far
+    input_bytes: usize,
+    max_input_bytes: usize,
+    output: mpsc::Receiver<Vec<u8>>,
+    /// Block of output, and how much of it was passed on
+    block: Vec<u8>,
+    pos: usize,
+    /// Taken once the pipeline has finished
+    handle: Option<JoinHandle<Result<(), XFlateError>>>,
+}
+
+impl Task {
+    fn spawn(pipeline: Pipeline, config: &XFlateConfig) -> Self {
+        let (input, chunks) = mpsc::unbounded_channel();
+        let (blocks, output) = mpsc::channel(OUTPUT_BLOCKS);
+
+        let max_input_bytes = pipeline.max_input_bytes(config);
+        let config = config.clone();
+        let input_reader = InputReader {
+            chunks,
+            chunk: Vec::new(),
+            pos: 0,
+        };
+
+        let handle = tokio::task::spawn_blocking(move || {
+            pipeline.run(config, input_reader, |block| {
+                blocks.blocking_send(block.to_vec()).map_err(|_| {
+                    XFlateError::XmlBError("Output of the pipeline was dropped".to_string())
+                })
+            })
+        });
+
+        Task {
+            input: Some(input),
+            input_bytes: 0,
+            max_input_bytes,
+            output,
+            block: Vec::new(),
+            pos: 0,
+            handle: Some(handle),
+        }
+    }
+
+    /// Pass input on to the pipeline, if it is still taken and
+    /// leaves the input no larger than the pipeline accepts
+    fn send_input(&mut self, data: &[u8]) -> io::Result<()> {
+        let Some(input) = &self.input else {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Write after shutdown",
+            ));
+        };
+
+        self.input_bytes += data.len();
+        check_input(self.input_bytes, self.max_input_bytes).map_err(io::Error::other)?;
+
+        // A pipeline that stopped early returns its error once polled
+        let _ = input.send(data.to_vec());
+
+        Ok(())
+    }
+
+    fn end_input(&mut self) {
+        self.input = None;
+    }
+
+    /// Wait for output that wasn't passed on yet. `None` once the
+    /// pipeline has finished and all of it was passed on.
+    fn poll_output(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<&[u8]>>> {
+        while self.pos == self.block.len() {
+            if let Some(block) = ready!(self.output.poll_recv(cx)) {
+                (self.block, self.pos) = (block, 0);
+                continue;
+            }
+
+            // The output ends when the pipeline returns
+            let Some(handle) = &mut self.handle else {
+                return Poll::Ready(Ok(None));
+            };
+            let result = ready!(Pin::new(handle).poll(cx));
+            self.handle = None;
+
+            result
+                .map_err(io::Error::other)?
+                .map_err(io::Error::other)?;
+        }
+
+        Poll::Ready(Ok(Some(&self.block[self.pos..])))
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.pos += len;
+    }
+}
+
+/// Reader returning the data of an inner reader, compressed or
+/// decompressed with XFlate.
+///
+/// The pipeline runs with `tokio::task::spawn_blocking`, so the reader
+/// must be used within a Tokio runtime. Errors from XFlate are
+/// returned as `io::Error`, wrapping the `XFlateError`.
+///
+/// XFlate needs the whole document, for its tables and checksum, so
+/// the inner reader is read to the end before anything is returned.
+/// The pipeline holds the input, up to what the configured limits allow
+/// a document to take: `max_output_bytes` of XML when compressing, or
+/// the largest frame `decompress` accepts. Past that, reading fails
+/// with `XFlateError::LimitExceeded`. The output is returned as it is
+/// produced: decompressed XML is decoded a block at a time, except
+/// for raw and lossless documents, which are only restored whole.
+pub struct XFlateAsyncReader<R> {
+    inner: R,
+    pipeline: Pipeline,
+    config: XFlateConfig,
+    /// Started on the first read, within the runtime
+    task: Option<Task>,
+}
+
+impl<R> XFlateAsyncReader<R> {
+    /// Read XML from `inner`, and return it compressed
+    pub fn compress(inner: R, config: XFlateConfig) -> Self {
+        XFlateAsyncReader::new(inner, Pipeline::Compress, config)
+    }
+
+    /// Read compressed data from `inner`, and return the XML
+    pub fn decompress(inner: R, config: XFlateConfig) -> Self {
+        XFlateAsyncReader::new(inner, Pipeline::Decompress, config)
+    }
+
+    fn new(inner: R, pipeline: Pipeline, config: XFlateConfig) -> Self {
+        XFlateAsyncReader {
+            inner,
+            pipeline,
+            config,
+            task: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> AsyncRead for XFlateAsyncReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let task = this
+            .task
+            .get_or_insert_with(|| Task::spawn(this.pipeline, &this.config));
+
+        while task.input.is_some() {
+            let mut chunk = [0; CHUNK_SIZE];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+
+            match chunk.filled() {
+                [] => task.end_input(),
+                data => task.send_input(data)?,
+            }
+        }
+
+        if let Some(output) = ready!(task.poll_output(cx))? {
+            let len = buf.remaining().min(output.len());
+            buf.put_slice(&output[..len]);
+            task.consume(len);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Writer compressing or decompressing the data written to it with
+/// XFlate, and writing the result to an inner writer.
+///
+/// Data is passed to the pipeline as it is written. The pipeline runs
+/// with `tokio::task::spawn_blocking`, so the writer must be used
+/// within a Tokio runtime. Errors from XFlate are returned as
+/// `io::Error`, wrapping the `XFlateError`.
+///
+/// Nothing comes out before the end of the input, so `shutdown` writes
+/// the result, as it is produced, and shuts the inner writer down. The
+/// input is held within the same bounds as for `XFlateAsyncReader`.
+/// Writing past them fails with `XFlateError::LimitExceeded`.
+pub struct XFlateAsyncWriter<W> {
+    inner: W,
+    pipeline: Pipeline,
+    config: XFlateConfig,
+    /// Started on the first write, within the runtime
+    task: Option<Task>,
+}
+
+impl<W> XFlateAsyncWriter<W> {
+    /// Take XML, and write it compressed to `inner`
+    pub fn compress(inner: W, config: XFlateConfig) -> Self {
+        XFlateAsyncWriter::new(inner, Pipeline::Compress, config)
+    }
+
+    /// Take compressed data, and write the XML to `inner`
+    pub fn decompress(inner: W, config: XFlateConfig) -> Self {
+        XFlateAsyncWriter::new(inner, Pipeline::Decompress, config)
+    }
+
+    fn new(inner: W, pipeline: Pipeline, config: XFlateConfig) -> Self {
+        XFlateAsyncWriter {
+            inner,
+            pipeline,
+            config,
+            task: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn task(&mut self) -> &mut Task {
+        self.task
+            .get_or_insert_with(|| Task::spawn(self.pipeline, &self.config))
+    }
+}
+
+impl<W> AsyncWrite for XFlateAsyncWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().task().send_input(buf)?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    /// Nothing can be written before the end of the input, so this
+    /// only flushes the inner writer
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let task = this
+            .task
+            .get_or_insert_with(|| Task::spawn(this.pipeline, &this.config));
+        task.end_input();
+
+        while let Some(output) = ready!(task.poll_output(cx))? {
+            let len = ready!(Pin::new(&mut this.inner).poll_write(cx, output))?;
+            if len == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            task.consume(len);
+        }
+
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
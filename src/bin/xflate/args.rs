@@ -0,0 +1,223 @@
+use std::{path::PathBuf, str::FromStr};
+
+use xflate::{XFlateMode, XFlateOutput, XmlBBackend, XmlBCompress, XmlBDeflateFraming};
+
+pub const USAGE: &str = "\
+Usage: xflate <command> [options] [files...]
+
+Commands:
+  compress [file]       Compress XML from a file or stdin
+  decompress [file]     Decompress data from a file or stdin
+  inspect [file]        Print the tables and stage sizes of compressed data
+  bench [paths...]      Compare with the plain backend and gzip on XML files or directories
+  train <files...>      Train a dictionary on sample documents
+
+Options:
+  -o, --output <file>       Write to a file instead of stdout
+  -s, --symbol-size <n>     Symbol size, inferred from the input by default
+  -l, --level <level>       Backend level: none, fast, best or 0 to 9 (default best)
+  -b, --backend <name>      Backend: deflate, zstd, brotli or lzma (default deflate)
+  -m, --mode <mode>         Encoding: xmln, raw or auto (default xmln)
+      --framing <framing>   Deflate framing: raw, zlib or gzip (default raw)
+      --window-bits <n>     Deflate window size, from 9 to 15
+      --no-tag-header       Leave out the tag header (output can't be decompressed)
+      --no-symbol-header    Leave out the symbol header (output can't be decompressed)
+  -d, --dictionary <file>   Dictionary made by `xflate train`
+      --best                Search for the configuration giving the smallest output
+      --verify              Decompress the output and check it matches the input
+      --checksum            Store a checksum, checked when decompressing
+      --lossless            Keep the input byte for byte, formatting included
+      --preserve-entities   Keep references to entities declared in the doctype
+      --no-indentation      Encode indentation character by character
+      --strip-whitespace    Leave out whitespace between elements (lossy)
+  -f, --format <format>     Decompressed layout: stored, compact, indented or
+                            canonical (default stored)
+      --indent <n|tab>      Indentation for the indented layout (default 2)
+  -n, --iterations <n>      Number of rounds for `bench` (default 10)
+  -h, --help                Print this message
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Compress,
+    Decompress,
+    Inspect,
+    Bench,
+    Train,
+}
+
+/// Parsed command line
+#[derive(Debug)]
+pub struct Args {
+    pub command: Command,
+    pub inputs: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub symbol_size: Option<u8>,
+    pub level: XmlBCompress,
+    pub backend: XmlBBackend,
+    pub mode: XFlateMode,
+    pub framing: XmlBDeflateFraming,
+    pub window_bits: Option<u8>,
+    pub tag_header: bool,
+    pub symbol_header: bool,
+    pub dictionary: Option<PathBuf>,
+    pub best: bool,
+    pub verify: bool,
+    pub checksum: bool,
+    pub lossless: bool,
+    pub preserve_entities: bool,
+    pub indentation: bool,
+    pub strip_whitespace: bool,
+    pub format: XFlateOutput,
+    pub iterations: usize,
+}
+
+impl Args {
+    /// Parse arguments, not including the program name.
+    /// Returns `Ok(None)` when help was requested.
+    pub fn parse<I>(args: I) -> Result<Option<Args>, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter();
+
+        let command = match args.next().as_deref() {
+            Some("compress") => Command::Compress,
+            Some("decompress") => Command::Decompress,
+            Some("inspect") => Command::Inspect,
+            Some("bench") => Command::Bench,
+            Some("train") => Command::Train,
+            Some("-h" | "--help") => return Ok(None),
+            Some(other) => return Err(format!("Unknown command: {}", other)),
+            None => return Err("Missing command".to_string()),
+        };
+
+        let mut format = None;
+        let mut indent = "  ".to_string();
+
+        let mut parsed = Args {
+            command,
+            inputs: Vec::new(),
+            output: None,
+            symbol_size: None,
+            level: XmlBCompress::Best,
+            backend: XmlBBackend::Deflate,
+            mode: XFlateMode::XmlN,
+            framing: XmlBDeflateFraming::Raw,
+            window_bits: None,
+            tag_header: true,
+            symbol_header: true,
+            dictionary: None,
+            best: false,
+            verify: false,
+            checksum: false,
+            lossless: false,
+            preserve_entities: false,
+            indentation: true,
+            strip_whitespace: false,
+            format: XFlateOutput::Stored,
+            iterations: 10,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", name))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-o" | "--output" => parsed.output = Some(value(&arg)?.into()),
+                "-s" | "--symbol-size" => parsed.symbol_size = Some(number(&arg, &value(&arg)?)?),
+                "-l" | "--level" => parsed.level = level(&value(&arg)?)?,
+                "-b" | "--backend" => {
+                    parsed.backend =
+                        XmlBBackend::from_str(&value(&arg)?).map_err(|e| e.to_string())?
+                }
+                "-m" | "--mode" => parsed.mode = mode(&value(&arg)?)?,
+                "--framing" => parsed.framing = framing(&value(&arg)?)?,
+                "--window-bits" => parsed.window_bits = Some(number(&arg, &value(&arg)?)?),
+                "--no-tag-header" => parsed.tag_header = false,
+                "--no-symbol-header" => parsed.symbol_header = false,
+                "-d" | "--dictionary" => parsed.dictionary = Some(value(&arg)?.into()),
+                "--best" => parsed.best = true,
+                "--verify" => parsed.verify = true,
+                "--checksum" => parsed.checksum = true,
+                "--lossless" => parsed.lossless = true,
+                "--preserve-entities" => parsed.preserve_entities = true,
+                "--no-indentation" => parsed.indentation = false,
+                "--strip-whitespace" => parsed.strip_whitespace = true,
+                "-f" | "--format" => format = Some(value(&arg)?),
+                "--indent" => indent = indentation(&value(&arg)?)?,
+                "-n" | "--iterations" => parsed.iterations = number(&arg, &value(&arg)?)?,
+                "-" => parsed.inputs.push(arg.into()),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+                _ => parsed.inputs.push(arg.into()),
+            }
+        }
+
+        if let Some(format) = format {
+            parsed.format = output(&format, indent)?;
+        }
+
+        match (parsed.command, parsed.inputs.len()) {
+            (Command::Train, 0) => Err("train needs at least one sample file".to_string()),
+            (Command::Train | Command::Bench, _) | (_, 0 | 1) => Ok(Some(parsed)),
+            (_, _) => Err("Only one input file is supported".to_string()),
+        }
+    }
+}
+
+fn number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number for {}: {}", name, value))
+}
+
+fn level(value: &str) -> Result<XmlBCompress, String> {
+    match value {
+        "none" => Ok(XmlBCompress::None),
+        "fast" => Ok(XmlBCompress::Fast),
+        "best" => Ok(XmlBCompress::Best),
+        _ => {
+            let level = XmlBCompress::Level(number("--level", value)?);
+            level.level().map_err(|e| e.to_string())?;
+            Ok(level)
+        }
+    }
+}
+
+fn mode(value: &str) -> Result<XFlateMode, String> {
+    match value {
+        "xmln" => Ok(XFlateMode::XmlN),
+        "raw" => Ok(XFlateMode::Raw),
+        "auto" => Ok(XFlateMode::Auto),
+        _ => Err(format!("Unknown mode: {}", value)),
+    }
+}
+
+fn framing(value: &str) -> Result<XmlBDeflateFraming, String> {
+    match value {
+        "raw" => Ok(XmlBDeflateFraming::Raw),
+        "zlib" => Ok(XmlBDeflateFraming::Zlib),
+        "gzip" => Ok(XmlBDeflateFraming::Gzip),
+        _ => Err(format!("Unknown framing: {}", value)),
+    }
+}
+
+fn output(value: &str, indent: String) -> Result<XFlateOutput, String> {
+    match value {
+        "stored" => Ok(XFlateOutput::Stored),
+        "compact" => Ok(XFlateOutput::Compact),
+        "indented" => Ok(XFlateOutput::Indented { indent }),
+        "canonical" => Ok(XFlateOutput::Canonical),
+        _ => Err(format!("Unknown format: {}", value)),
+    }
+}
+
+fn indentation(value: &str) -> Result<String, String> {
+    match value {
+        "tab" => Ok("\t".to_string()),
+        _ => Ok(" ".repeat(number("--indent", value)?)),
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    fmt::Write,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use xflate::{MAX_SYMBOL_CODE_SIZE, XFlate, XFlateConfig, XFlateError, XFlateMode};
+
+use crate::peak::PEAK;
+
+/// Number of symbol sizes compared, starting at the smallest usable one
+const SYMBOL_SIZE_SWEEP: u8 = 4;
+
+/// Document to benchmark
+pub struct Sample {
+    pub name: String,
+    pub xml: Vec<u8>,
+}
+
+impl Sample {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let xml = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |n| n.to_string_lossy().into(),
+        );
+
+        Ok(Sample { name, xml })
+    }
+}
+
+/// Read the given files, and every `.xml` file in the given directories
+pub fn load(paths: &[PathBuf]) -> Result<Vec<Sample>, String> {
+    let mut samples = Vec::new();
+
+    for path in paths {
+        if !path.is_dir() {
+            samples.push(Sample::read(path)?);
+            continue;
+        }
+
+        let mut files = fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|e| e.map(|e| e.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        files.retain(|file| file.is_file() && file.extension().is_some_and(|e| e == "xml"));
+        files.sort();
+
+        for file in files {
+            samples.push(Sample::read(&file)?);
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Sizes, times and memory use of compressing one or more documents
+#[derive(Debug, Clone, Copy, Default)]
+struct Measurement {
+    input_bytes: usize,
+    output_bytes: usize,
+    iterations: usize,
+    compress_time: Duration,
+    decompress_time: Duration,
+    peak_bytes: usize,
+}
+
+impl Measurement {
+    fn take<C, D>(xml: &[u8], iterations: usize, compress: C, decompress: D) -> Result<Self, String>
+    where
+        C: Fn(&[u8]) -> Result<Vec<u8>, XFlateError>,
+        D: Fn(&[u8]) -> Result<(), XFlateError>,
+    {
+        let mut measurement = Measurement {
+            input_bytes: xml.len(),
+            iterations,
+            ..Default::default()
+        };
+
+        for _ in 0..iterations {
+            let start = Instant::now();
+            let (compressed, peak) = PEAK.measure(|| compress(xml));
+            measurement.compress_time += start.elapsed();
+            let compressed = compressed.map_err(describe)?;
+
+            let start = Instant::now();
+            let (decompressed, decompress_peak) = PEAK.measure(|| decompress(&compressed));
+            measurement.decompress_time += start.elapsed();
+            decompressed.map_err(describe)?;
+
+            measurement.output_bytes = compressed.len();
+            measurement.peak_bytes = measurement.peak_bytes.max(peak).max(decompress_peak);
+        }
+
+        Ok(measurement)
+    }
+
+    /// Combine with the measurement of another document
+    fn add(&mut self, other: &Measurement) {
+        self.input_bytes += other.input_bytes;
+        self.output_bytes += other.output_bytes;
+        self.iterations = other.iterations;
+        self.compress_time += other.compress_time;
+        self.decompress_time += other.decompress_time;
+        self.peak_bytes = self.peak_bytes.max(other.peak_bytes);
+    }
+
+    fn ratio(&self) -> f64 {
+        ratio(self.output_bytes, self.input_bytes)
+    }
+
+    fn compress_speed(&self) -> f64 {
+        throughput(self.input_bytes * self.iterations, self.compress_time)
+    }
+
+    fn decompress_speed(&self) -> f64 {
+        throughput(self.input_bytes * self.iterations, self.decompress_time)
+    }
+}
+
+/// Benchmark XFlate on each sample against two baselines: the
+/// configured backend on its own, with the same options, and gzip
+/// at the same level.
+///
+/// The symbol size of `config` is used if `infer_symbol_size` is false,
+/// otherwise the smallest usable size is inferred for each sample.
+/// Returns a report with a row per sample and one for all of them.
+pub fn run(
+    samples: &[Sample],
+    config: &XFlateConfig,
+    infer_symbol_size: bool,
+    iterations: usize,
+) -> Result<String, String> {
+    let iterations = iterations.max(1);
+    // The raw encoding only adds the two bytes naming it
+    let backend = XFlateConfig {
+        mode: XFlateMode::Raw,
+        checksum: false,
+        verify: false,
+        ..config.clone()
+    };
+    let level = Compression::new(config.xmlb_opt.level().map_err(describe)?);
+
+    let mut report = String::new();
+    let mut totals = [Measurement::default(); 3];
+    let mut min_sizes = Vec::with_capacity(samples.len());
+
+    writeln!(
+        report,
+        "{:<24} {:>10} {:>8} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>9} {:>9} {:>9}",
+        "File",
+        "Bytes",
+        "XFlate",
+        "Backend",
+        "Gzip",
+        "XF comp",
+        "BE comp",
+        "GZ comp",
+        "XF decomp",
+        "BE decomp",
+        "GZ decomp",
+        "XF peak",
+        "BE peak",
+        "GZ peak"
+    )
+    .unwrap();
+
+    for sample in samples {
+        let min_size = XFlateConfig::infer_symbol_size(sample.xml.as_slice())
+            .map_err(|e| format!("{}: {}", sample.name, describe(e)))?
+            .symbol_size;
+        min_sizes.push(min_size);
+
+        let mut config = config.clone();
+        if infer_symbol_size {
+            config.symbol_size = min_size;
+        }
+
+        let xflate = Measurement::take(
+            &sample.xml,
+            iterations,
+            |xml| XFlate::new(config.clone()).compress(xml),
+            |buf| XFlate::new(config.clone()).decompress(buf).map(|_| ()),
+        );
+        let raw = Measurement::take(
+            &sample.xml,
+            iterations,
+            |xml| XFlate::new(backend.clone()).compress(xml),
+            |buf| XFlate::new(backend.clone()).decompress(buf).map(|_| ()),
+        );
+        let gzip = Measurement::take(
+            &sample.xml,
+            iterations,
+            |xml| gzip_compress(xml, level),
+            |buf| gzip_decompress(buf).map(|_| ()),
+        );
+
+        let measurements = [xflate, raw, gzip]
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("{}: {}", sample.name, e))?;
+
+        write_row(&mut report, &sample.name, &measurements);
+        for (total, measurement) in totals.iter_mut().zip(&measurements) {
+            total.add(measurement);
+        }
+    }
+
+    if samples.len() > 1 {
+        write_row(&mut report, "Total", &totals);
+    }
+
+    writeln!(
+        report,
+        "\nBackend is {} on its own, with the same options. Gzip is at the same level.\n\
+         Ratios are output over input size. Speeds are in MB/s, peaks in KiB.",
+        config.xmlb_backend
+    )
+    .unwrap();
+
+    write_symbol_sizes(&mut report, samples, config, &min_sizes)?;
+
+    Ok(report)
+}
+
+/// Row of XFlate, backend and gzip measurements, in that order
+fn write_row(report: &mut String, name: &str, measurements: &[Measurement]) {
+    write!(report, "{:<24} {:>10}", name, measurements[0].input_bytes).unwrap();
+
+    for m in measurements {
+        write!(report, " {:>7.2}%", m.ratio()).unwrap();
+    }
+    for m in measurements {
+        write!(report, " {:>10.2}", m.compress_speed()).unwrap();
+    }
+    for m in measurements {
+        write!(report, " {:>10.2}", m.decompress_speed()).unwrap();
+    }
+    for m in measurements {
+        write!(report, " {:>9}", m.peak_bytes.div_ceil(1024)).unwrap();
+    }
+
+    writeln!(report).unwrap();
+}
+
+fn gzip_compress(xml: &[u8], level: Compression) -> Result<Vec<u8>, XFlateError> {
+    let mut encoder = GzEncoder::new(Vec::new(), level);
+    io::Write::write_all(&mut encoder, xml)
+        .and_then(|_| encoder.finish())
+        .map_err(gzip_error)
+}
+
+fn gzip_decompress(buf: &[u8]) -> Result<Vec<u8>, XFlateError> {
+    let mut xml = Vec::new();
+    GzDecoder::new(buf)
+        .read_to_end(&mut xml)
+        .map_err(gzip_error)?;
+
+    Ok(xml)
+}
+
+fn gzip_error(err: io::Error) -> XFlateError {
+    XFlateError::XmlBError(format!("gzip: {}", err))
+}
+
+/// Compression ratio of each sample as the symbol size grows.
+/// Sizes too small for a sample are left blank.
+fn write_symbol_sizes(
+    report: &mut String,
+    samples: &[Sample],
+    config: &XFlateConfig,
+    min_sizes: &[u8],
+) -> Result<(), String> {
+    let Some(&first) = min_sizes.iter().min() else {
+        return Ok(());
+    };
+    let last = (first + SYMBOL_SIZE_SWEEP - 1).min(MAX_SYMBOL_CODE_SIZE);
+    let sizes = first..=last;
+
+    write!(report, "\nRatio by symbol size\n{:<24}", "File").unwrap();
+    for size in sizes.clone() {
+        write!(report, " {:>8}", size).unwrap();
+    }
+    writeln!(report).unwrap();
+
+    let mut totals = vec![(0, 0); sizes.len()];
+
+    for (sample, &min_size) in samples.iter().zip(min_sizes) {
+        write!(report, "{:<24}", sample.name).unwrap();
+
+        for (size, total) in sizes.clone().zip(&mut totals) {
+            if size < min_size {
+                write!(report, " {:>8}", "-").unwrap();
+                continue;
+            }
+
+            let config = XFlateConfig {
+                symbol_size: size,
+                ..config.clone()
+            };
+            let compressed = XFlate::new(config)
+                .compress(sample.xml.as_slice())
+                .map_err(|e| format!("{}: {}", sample.name, describe(e)))?;
+
+            total.0 += compressed.len();
+            total.1 += sample.xml.len();
+            write!(
+                report,
+                " {:>7.2}%",
+                ratio(compressed.len(), sample.xml.len())
+            )
+            .unwrap();
+        }
+
+        writeln!(report).unwrap();
+    }
+
+    if samples.len() > 1 {
+        write!(report, "{:<24}", "Total").unwrap();
+        for (output, input) in totals {
+            write!(report, " {:>7.2}%", ratio(output, input)).unwrap();
+        }
+        writeln!(report).unwrap();
+    }
+
+    Ok(())
+}
+
+fn ratio(output: usize, input: usize) -> f64 {
+    output as f64 / input.max(1) as f64 * 100.0
+}
+
+fn throughput(bytes: usize, time: Duration) -> f64 {
+    bytes as f64 / 1_000_000.0 / time.as_secs_f64().max(f64::EPSILON)
+}
+
+fn describe(err: XFlateError) -> String {
+    err.to_string()
+}
@@ -0,0 +1,197 @@
+mod args;
+mod bench;
+mod peak;
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+    process::ExitCode,
+};
+
+use xflate::{XFlate, XFlateConfig, XFlateDictionary, XFlateError, XmlNEntities};
+
+use crate::args::{Args, Command, USAGE};
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("xflate: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("xflate: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    match args.command {
+        Command::Compress => compress(args),
+        Command::Decompress => decompress(args),
+        Command::Inspect => inspect(args),
+        Command::Bench => bench(args),
+        Command::Train => train(args),
+    }
+}
+
+fn compress(args: &Args) -> Result<(), String> {
+    let xml = read_input(args.inputs.first())?;
+    let mut xflate = XFlate::new(config(args, &xml)?);
+
+    let compressed = if args.best {
+        xflate.compress_best(xml.as_slice())
+    } else {
+        xflate.compress(xml.as_slice())
+    }
+    .map_err(describe)?;
+
+    write_output(args.output.as_deref(), &compressed)
+}
+
+fn decompress(args: &Args) -> Result<(), String> {
+    let compressed = read_input(args.inputs.first())?;
+    let mut xflate = XFlate::new(config(args, &[])?);
+
+    let xml = xflate
+        .decompress_with(compressed.as_slice(), &args.format)
+        .map_err(describe)?;
+
+    write_output(args.output.as_deref(), xml.as_bytes())
+}
+
+fn inspect(args: &Args) -> Result<(), String> {
+    let compressed = read_input(args.inputs.first())?;
+    let mut xflate = XFlate::new(config(args, &[])?);
+
+    let stats = xflate.inspect(compressed.as_slice()).map_err(describe)?;
+
+    let report = format!(
+        "{}\n\n{}\n\n{}\n",
+        stats,
+        xflate.tag_table(),
+        xflate.sym_table()
+    );
+    write_output(args.output.as_deref(), report.as_bytes())
+}
+
+fn bench(args: &Args) -> Result<(), String> {
+    let samples = match args.inputs.as_slice() {
+        [] => vec![bench::Sample {
+            name: "<stdin>".to_string(),
+            xml: read_input(None::<&Path>)?,
+        }],
+        inputs => bench::load(inputs)?,
+    };
+    let config = config(args, &[])?;
+
+    let report = bench::run(
+        &samples,
+        &config,
+        args.symbol_size.is_none(),
+        args.iterations,
+    )?;
+    write_output(args.output.as_deref(), report.as_bytes())
+}
+
+fn train(args: &Args) -> Result<(), String> {
+    let samples = args
+        .inputs
+        .iter()
+        .map(|path| read_input(Some(path)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let dictionary = XFlateDictionary::train(&samples).map_err(describe)?;
+
+    write_output(args.output.as_deref(), &dictionary.to_bytes())
+}
+
+/// Build the XFlate configuration from the options.
+/// The symbol size is inferred from `xml` unless given.
+fn config(args: &Args, xml: &[u8]) -> Result<XFlateConfig, String> {
+    let symbol_size = match args.symbol_size {
+        Some(size) => size,
+        None if xml.is_empty() => XFlateConfig::default().symbol_size,
+        None => {
+            XFlateConfig::infer_symbol_size(xml)
+                .map_err(describe)?
+                .symbol_size
+        }
+    };
+
+    let dictionary = match &args.dictionary {
+        Some(path) => {
+            let bytes = read_input(Some(path))?;
+            Some(XFlateDictionary::from_bytes(&bytes).map_err(describe)?)
+        }
+        None => None,
+    };
+
+    Ok(XFlateConfig {
+        symbol_size,
+        xmlb_opt: args.level,
+        xmlb_backend: args.backend,
+        xmlb_framing: args.framing,
+        xmlb_window_bits: args.window_bits,
+        add_symbol_header: args.symbol_header,
+        add_tag_header: args.tag_header,
+        mode: args.mode,
+        dictionary,
+        verify: args.verify,
+        checksum: args.checksum,
+        lossless: args.lossless,
+        entities: if args.preserve_entities {
+            XmlNEntities::Preserve
+        } else {
+            XmlNEntities::Expand
+        },
+        indentation: args.indentation,
+        strip_whitespace: args.strip_whitespace,
+        ..Default::default()
+    })
+}
+
+/// Read a whole file, or stdin when no file or `-` is given
+fn read_input<P: AsRef<Path>>(path: Option<P>) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+
+    match path {
+        Some(path) if path.as_ref() != Path::new("-") => {
+            let path = path.as_ref();
+            data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        _ => {
+            io::stdin()
+                .read_to_end(&mut data)
+                .map_err(|e| format!("stdin: {}", e))?;
+        }
+    }
+
+    Ok(data)
+}
+
+/// Write to a file, or stdout when no file or `-` is given
+fn write_output(path: Option<&Path>, data: &[u8]) -> Result<(), String> {
+    match path {
+        Some(path) if path != Path::new("-") => {
+            fs::write(path, data).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        _ => io::stdout()
+            .write_all(data)
+            .map_err(|e| format!("stdout: {}", e)),
+    }
+}
+
+fn describe(err: XFlateError) -> String {
+    err.to_string()
+}
@@ -0,0 +1,471 @@
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor,
+    value::{StrDeserializer, UnitDeserializer},
+};
+use xml::reader::XmlEvent;
+
+use crate::{
+    XFlate, XFlateConfig, XFlateError,
+    ser::{ENTRY, ITEM, KEY, NIL, VALUE},
+};
+
+/// Deserialize a value from the XFlate format, as written by `to_vec`.
+///
+/// The document is decoded with `XFlate::decompress_events` into
+/// elements, without rendering it as text. Struct fields are matched
+/// to child elements by name. A missing element, or one marked with
+/// `nil="true"`, reads as `None`.
+pub fn from_slice<T>(bytes: &[u8]) -> Result<T, XFlateError>
+where
+    T: DeserializeOwned,
+{
+    let events = XFlate::new(XFlateConfig::default()).decompress_events(bytes)?;
+    let root = read_tree(events)?;
+
+    T::deserialize(Deserializer(&root))
+}
+
+impl de::Error for XFlateError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        XFlateError::SerdeError(msg.to_string())
+    }
+}
+
+/// Element read from the document, with its text run together
+struct Element {
+    name: String,
+    text: String,
+    children: Vec<Element>,
+    /// Whether the element holds `None`
+    nil: bool,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+}
+
+/// Read the events into the tree of the root element
+fn read_tree<I>(events: I) -> Result<Element, XFlateError>
+where
+    I: IntoIterator<Item = Result<XmlEvent, XFlateError>>,
+{
+    let mut stack: Vec<Element> = Vec::new();
+
+    for e in events {
+        match e? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(Element {
+                name: name.local_name,
+                text: String::new(),
+                children: Vec::new(),
+                nil: attributes
+                    .iter()
+                    .any(|attr| attr.name.local_name == NIL && attr.value == "true"),
+            }),
+
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().ok_or_else(|| {
+                    XFlateError::SerdeError("Unmatched closing tag found".to_string())
+                })?;
+
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+
+            XmlEvent::Characters(data) | XmlEvent::Whitespace(data) | XmlEvent::CData(data) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&data);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    Err(XFlateError::SerdeError(
+        "Document has no root element".to_string(),
+    ))
+}
+
+/// Reads a value from an element
+struct Deserializer<'a>(&'a Element);
+
+impl Deserializer<'_> {
+    fn parse<T>(&self) -> Result<T, XFlateError>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.0.text.parse().map_err(|e| {
+            XFlateError::SerdeError(format!("Invalid value in <{}>: {}", self.0.name, e))
+        })
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Deserializer<'a> {
+    type Error = XFlateError;
+
+    /// Elements holding others read as maps, and the others as text
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.0.nil {
+            visitor.visit_none()
+        } else if self.0.children.is_empty() {
+            visitor.visit_str(&self.0.text)
+        } else {
+            visitor.visit_map(Fields::new(self.0))
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_str(&self.0.text)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.0.text.clone())
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        let bytes = self
+            .0
+            .children
+            .iter()
+            .map(|item| Deserializer(item).parse())
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        visitor.visit_byte_buf(bytes)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// A missing element is read as `None` by the struct holding it
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.0.nil {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(Items(self.0.children.iter()))
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(Entries {
+            entries: self.0.children.iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(Fields::new(self.0))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(Variant(self.0))
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+/// Items of a sequence or tuple, from the `item` children
+struct Items<'a>(std::slice::Iter<'a, Element>);
+
+impl<'de> de::SeqAccess<'de> for Items<'_> {
+    type Error = XFlateError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, XFlateError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.0.find(|child| child.name == ITEM) {
+            Some(item) => seed.deserialize(Deserializer(item)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Fields of a struct, keyed by the names of the children
+struct Fields<'a> {
+    children: std::slice::Iter<'a, Element>,
+    value: Option<&'a Element>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(element: &'a Element) -> Self {
+        Fields {
+            children: element.children.iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Fields<'_> {
+    type Error = XFlateError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, XFlateError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let Some(child) = self.children.next() else {
+            return Ok(None);
+        };
+        self.value = Some(child);
+
+        let key: StrDeserializer<XFlateError> = child.name.as_str().into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, XFlateError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| XFlateError::SerdeError("Value read before its key".to_string()))?;
+
+        seed.deserialize(Deserializer(value))
+    }
+}
+
+/// Entries of a map, from the `entry` children holding
+/// a `key` and a `value`
+struct Entries<'a> {
+    entries: std::slice::Iter<'a, Element>,
+    value: Option<Option<&'a Element>>,
+}
+
+impl<'de> de::MapAccess<'de> for Entries<'_> {
+    type Error = XFlateError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, XFlateError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let Some(entry) = self.entries.find(|child| child.name == ENTRY) else {
+            return Ok(None);
+        };
+        let key = entry
+            .child(KEY)
+            .ok_or_else(|| XFlateError::SerdeError(format!("Map entry without a <{}>", KEY)))?;
+        self.value = Some(entry.child(VALUE));
+
+        seed.deserialize(Deserializer(key)).map(Some)
+    }
+
+    /// A `None` value leaves the `value` element out
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, XFlateError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(Some(value)) => seed.deserialize(Deserializer(value)),
+            Some(None) => seed.deserialize(UnitDeserializer::<XFlateError>::new()),
+            None => Err(XFlateError::SerdeError(
+                "Value read before its key".to_string(),
+            )),
+        }
+    }
+}
+
+/// Enum held by an element. A variant with content is the only
+/// child, named after it, while a unit variant is written as text.
+struct Variant<'a>(&'a Element);
+
+impl<'de, 'a> de::EnumAccess<'de> for Variant<'a> {
+    type Error = XFlateError;
+    type Variant = VariantContent<'a>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantContent<'a>), XFlateError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (name, content) = match self.0.children.first() {
+            Some(child) => (child.name.as_str(), Some(child)),
+            None => (self.0.text.as_str(), None),
+        };
+
+        let name: StrDeserializer<XFlateError> = name.into_deserializer();
+        Ok((seed.deserialize(name)?, VariantContent(content)))
+    }
+}
+
+struct VariantContent<'a>(Option<&'a Element>);
+
+impl VariantContent<'_> {
+    fn content(&self) -> Result<&Element, XFlateError> {
+        self.0
+            .ok_or_else(|| XFlateError::SerdeError("Variant has no content".to_string()))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantContent<'_> {
+    type Error = XFlateError;
+
+    fn unit_variant(self) -> Result<(), XFlateError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, XFlateError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(Deserializer(self.content()?))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(Deserializer(self.content()?), visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, XFlateError>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_struct(Deserializer(self.content()?), "", &[], visitor)
+    }
+}
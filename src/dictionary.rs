@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use crate::{
+    MAX_DEFLATE_DICTIONARY_SIZE, XFlate, XFlateError, XFlateLimits, XmlNDynamicSymbolTable,
+    XmlNDynamicTagTable, XmlNSymbolTable, XmlNTagTable, encode_xmln, encode_xmls,
+    min_symbol_code_size, scan::scan,
+};
+
+/// Dictionary trained on a family of similar documents.
+///
+/// A dictionary fixes the codes of known tags and characters, and
+/// primes the deflate window with XMLS data encoded using those
+/// codes. Small documents then compress mostly into references to
+/// the dictionary. The same dictionary must be used to decompress.
+#[derive(Debug, Clone)]
+pub struct XFlateDictionary {
+    /// Symbol table new documents start from
+    sym_table: XmlNDynamicSymbolTable,
+    /// Tag table new documents start from
+    tag_table: XmlNDynamicTagTable,
+    /// Preset dictionary for the deflate backend
+    data: Vec<u8>,
+}
+
+impl XFlateDictionary {
+    pub fn new(
+        sym_table: XmlNDynamicSymbolTable,
+        tag_table: XmlNDynamicTagTable,
+        data: Vec<u8>,
+    ) -> Self {
+        XFlateDictionary {
+            sym_table,
+            tag_table,
+            data,
+        }
+    }
+
+    /// Train a dictionary on sample documents.
+    ///
+    /// Tags and characters are given codes by their frequency
+    /// across all samples. The deflate dictionary holds the XMLS
+    /// encoding of the samples followed by the table headers,
+    /// keeping the last `MAX_DEFLATE_DICTIONARY_SIZE` bytes.
+    pub fn train<D>(samples: &[D]) -> Result<Self, XFlateError>
+    where
+        D: AsRef<[u8]>,
+    {
+        let mut tags: Vec<(String, usize)> = Vec::new();
+        let mut symbols: Vec<(char, usize)> = Vec::new();
+        let mut tag_index: HashMap<String, usize> = HashMap::new();
+        let mut symbol_index: HashMap<char, usize> = HashMap::new();
+
+        for sample in samples {
+            let scan = scan(sample.as_ref())?;
+
+            for (tag, count) in scan.tags {
+                let i = *tag_index.entry(tag.clone()).or_insert_with(|| {
+                    tags.push((tag, 0));
+                    tags.len() - 1
+                });
+                tags[i].1 += count;
+            }
+
+            for (symbol, count) in scan.symbols {
+                let i = *symbol_index.entry(symbol).or_insert_with(|| {
+                    symbols.push((symbol, 0));
+                    symbols.len() - 1
+                });
+                symbols[i].1 += count;
+            }
+        }
+
+        // Stable, so ties keep their first-seen order
+        tags.sort_by(|(_, a), (_, b)| b.cmp(a));
+        symbols.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        let code_size = min_symbol_code_size(symbols.len()).ok_or_else(|| {
+            XFlateError::PrePassError(format!(
+                "No symbol size can encode {} unique symbols",
+                symbols.len()
+            ))
+        })?;
+
+        let mut tag_table = XmlNDynamicTagTable::new();
+        for (tag, _) in &tags {
+            tag_table
+                .encode(tag)
+                .ok_or_else(|| XFlateError::XmlNError(format!("Failed to encode tag: {}", tag)))?;
+        }
+
+        let mut sym_table = XmlNDynamicSymbolTable::new(code_size);
+        for &(symbol, _) in &symbols {
+            sym_table.encode(symbol).ok_or_else(|| {
+                XFlateError::XmlNError(format!("Failed to encode symbol: {}", symbol))
+            })?;
+        }
+
+        let mut data = Vec::new();
+        for sample in samples {
+            let xmln = encode_xmln(
+                sample.as_ref(),
+                &mut sym_table.clone(),
+                &mut tag_table.clone(),
+            )?;
+            data.extend(encode_xmls(&xmln)?);
+        }
+
+        // Compressed documents start with these headers
+        data.extend(tag_table.to_header());
+        data.extend(sym_table.to_header());
+
+        let start = data.len().saturating_sub(MAX_DEFLATE_DICTIONARY_SIZE);
+        data.drain(..start);
+
+        Ok(XFlateDictionary::new(sym_table, tag_table, data))
+    }
+
+    pub fn sym_table(&self) -> &XmlNDynamicSymbolTable {
+        &self.sym_table
+    }
+
+    pub fn tag_table(&self) -> &XmlNDynamicTagTable {
+        &self.tag_table
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Serialize the dictionary, so it can be stored in a file.
+    /// The tag and symbol headers are followed by the deflate dictionary.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.tag_table.to_header();
+        bytes.extend(self.sym_table.to_header());
+        bytes.extend(&self.data);
+        bytes
+    }
+
+    /// Deserialize a dictionary written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, XFlateError> {
+        let (tag_table, sym_table, data) = XFlate::split_headers(bytes, &XFlateLimits::default())?;
+
+        Ok(XFlateDictionary::new(sym_table, tag_table, data.to_vec()))
+    }
+}
@@ -0,0 +1,344 @@
+use xml::{
+    attribute::OwnedAttribute,
+    common::XmlVersion,
+    name::OwnedName,
+    namespace::{NS_NO_PREFIX, Namespace, NamespaceStack},
+    reader::XmlEvent,
+};
+
+use crate::XFlateError;
+
+/// Owned tree of a document, returned by `XFlate::decompress_document`
+/// and compressed by `XFlate::compress_document`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XFlateDocument {
+    /// Document type declaration, as written in the document
+    pub doctype: Option<String>,
+    /// Comments and processing instructions before the root element
+    pub prolog: Vec<Node>,
+    pub root: Element,
+    /// Comments and processing instructions after the root element
+    pub epilog: Vec<Node>,
+}
+
+/// Node held by an element, or around the root element
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+    Comment(String),
+    ProcessingInstruction { name: String, data: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: OwnedName,
+    pub attributes: Vec<Attribute>,
+    /// Namespaces bound on the element or its ancestors. Those that
+    /// differ from the parent's are declared on the element when written.
+    pub namespace: Namespace,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub name: OwnedName,
+    pub value: String,
+}
+
+impl XFlateDocument {
+    pub fn new(root: Element) -> Self {
+        XFlateDocument {
+            doctype: None,
+            prolog: Vec::new(),
+            root,
+            epilog: Vec::new(),
+        }
+    }
+
+    /// Build the tree from the events of a document. Whitespace
+    /// outside the root element is left out.
+    pub fn from_events<I>(events: I, doctype: Option<String>) -> Result<Self, XFlateError>
+    where
+        I: IntoIterator<Item = Result<XmlEvent, XFlateError>>,
+    {
+        let mut prolog = Vec::new();
+        let mut root = None;
+        let mut epilog = Vec::new();
+        let mut stack: Vec<Element> = Vec::new();
+
+        for e in events {
+            let node = match e? {
+                XmlEvent::StartElement {
+                    name,
+                    attributes,
+                    namespace,
+                } => {
+                    stack.push(Element {
+                        name,
+                        attributes: attributes.into_iter().map(Attribute::from).collect(),
+                        namespace,
+                        children: Vec::new(),
+                    });
+                    continue;
+                }
+
+                XmlEvent::EndElement { .. } => match stack.pop() {
+                    Some(element) => Node::Element(element),
+                    None => {
+                        return Err(XFlateError::XmlNError(
+                            "Unmatched closing tag found".to_string(),
+                        ));
+                    }
+                },
+
+                XmlEvent::Characters(data) | XmlEvent::Whitespace(data) | XmlEvent::CData(data) => {
+                    let Some(parent) = stack.last_mut() else {
+                        continue;
+                    };
+
+                    match parent.children.last_mut() {
+                        Some(Node::Text(text)) => text.push_str(&data),
+                        _ => parent.children.push(Node::Text(data)),
+                    }
+                    continue;
+                }
+
+                XmlEvent::Comment(data) => Node::Comment(data),
+                XmlEvent::ProcessingInstruction { name, data } => {
+                    Node::ProcessingInstruction { name, data }
+                }
+                XmlEvent::StartDocument { .. } | XmlEvent::EndDocument => continue,
+            };
+
+            match (stack.last_mut(), node) {
+                (Some(parent), node) => parent.children.push(node),
+                (None, Node::Element(element)) if root.is_none() => root = Some(element),
+                (None, Node::Element(_)) => {
+                    return Err(XFlateError::XmlNError(
+                        "Document has more than one root element".to_string(),
+                    ));
+                }
+                (None, node) if root.is_none() => prolog.push(node),
+                (None, node) => epilog.push(node),
+            }
+        }
+
+        if let Some(element) = stack.last() {
+            return Err(XFlateError::XmlNError(format!(
+                "Element {} is never closed",
+                element.name
+            )));
+        }
+
+        let root =
+            root.ok_or_else(|| XFlateError::XmlNError("Document has no root element".to_string()))?;
+
+        Ok(XFlateDocument {
+            doctype,
+            prolog,
+            root,
+            epilog,
+        })
+    }
+
+    /// Events a parser would read from the document
+    pub fn to_events(&self) -> Vec<XmlEvent> {
+        let mut events = vec![XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: "UTF-8".to_string(),
+            standalone: None,
+        }];
+
+        let namespace = NamespaceStack::default().squash();
+        for node in &self.prolog {
+            node.push_events(&mut events, &namespace);
+        }
+        self.root.push_events(&mut events, &namespace);
+        for node in &self.epilog {
+            node.push_events(&mut events, &namespace);
+        }
+
+        events.push(XmlEvent::EndDocument);
+        events
+    }
+}
+
+impl Node {
+    fn push_events(&self, events: &mut Vec<XmlEvent>, parent: &Namespace) {
+        let event = match self {
+            Node::Element(element) => return element.push_events(events, parent),
+            Node::Text(text) if text.is_empty() => return,
+            Node::Text(text) if is_whitespace(text) => XmlEvent::Whitespace(text.clone()),
+            Node::Text(text) => XmlEvent::Characters(text.clone()),
+            Node::Comment(text) => XmlEvent::Comment(text.clone()),
+            Node::ProcessingInstruction { name, data } => XmlEvent::ProcessingInstruction {
+                name: name.clone(),
+                data: data.clone(),
+            },
+        };
+
+        events.push(event);
+    }
+}
+
+impl Element {
+    /// Element without attributes or content. A prefixed name such
+    /// as `p:local` keeps its prefix, which must be bound in
+    /// `namespace` or by an ancestor.
+    pub fn new(name: &str) -> Self {
+        Element {
+            name: parse_name(name),
+            attributes: Vec::new(),
+            namespace: Namespace::empty(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Value of the attribute named `name`, matched as by `child`
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attr| is_named(&attr.name, name))
+            .map(|attr| attr.value.as_str())
+    }
+
+    /// Set the value of the attribute named `name`, adding it if missing
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        match self
+            .attributes
+            .iter_mut()
+            .find(|attr| is_named(&attr.name, name))
+        {
+            Some(attr) => attr.value = value.to_string(),
+            None => self.attributes.push(Attribute::new(name, value)),
+        }
+    }
+
+    /// First child element named `name`. A name without a prefix
+    /// matches the local name, whatever the namespace.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements()
+            .find(|element| is_named(&element.name, name))
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut Element> {
+        self.children.iter_mut().find_map(|child| match child {
+            Node::Element(element) if is_named(&element.name, name) => Some(element),
+            _ => None,
+        })
+    }
+
+    /// Child elements named `name`, matched as by `child`
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements()
+            .filter(move |element| is_named(&element.name, name))
+    }
+
+    /// Child elements, leaving out text and other nodes
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    /// Text of the element and its descendants, run together
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.push_text(&mut text);
+        text
+    }
+
+    fn push_text(&self, out: &mut String) {
+        for child in &self.children {
+            match child {
+                Node::Element(element) => element.push_text(out),
+                Node::Text(text) => out.push_str(text),
+                _ => {}
+            }
+        }
+    }
+
+    /// Push the events of the element. Names are resolved against the
+    /// namespaces in scope, so elements built without a namespace URI
+    /// get the one their prefix, or the default namespace, is bound to.
+    fn push_events(&self, events: &mut Vec<XmlEvent>, parent: &Namespace) {
+        let mut namespace = parent.clone();
+        for (prefix, uri) in &self.namespace {
+            namespace.force_put(prefix, uri);
+        }
+
+        let name = resolve(&self.name, &namespace, true);
+        events.push(XmlEvent::StartElement {
+            name: name.clone(),
+            attributes: self
+                .attributes
+                .iter()
+                .map(|attr| OwnedAttribute {
+                    name: resolve(&attr.name, &namespace, false),
+                    value: attr.value.clone(),
+                })
+                .collect(),
+            namespace: namespace.clone(),
+        });
+
+        for child in &self.children {
+            child.push_events(events, &namespace);
+        }
+
+        events.push(XmlEvent::EndElement { name });
+    }
+}
+
+impl Attribute {
+    pub fn new(name: &str, value: &str) -> Self {
+        Attribute {
+            name: parse_name(name),
+            value: value.to_string(),
+        }
+    }
+}
+
+impl From<OwnedAttribute> for Attribute {
+    fn from(attr: OwnedAttribute) -> Self {
+        Attribute {
+            name: attr.name,
+            value: attr.value,
+        }
+    }
+}
+
+fn parse_name(name: &str) -> OwnedName {
+    name.parse().unwrap_or_else(|_| OwnedName::local(name))
+}
+
+/// Bind a name to the namespace of its prefix. Unprefixed attributes
+/// have no namespace, while elements are in the default namespace.
+fn resolve(name: &OwnedName, namespace: &Namespace, element: bool) -> OwnedName {
+    let uri = match name.prefix.as_deref() {
+        Some(prefix) => namespace.get(prefix),
+        None if element => namespace.get(NS_NO_PREFIX).filter(|uri| !uri.is_empty()),
+        None => None,
+    };
+
+    OwnedName {
+        local_name: name.local_name.clone(),
+        namespace: uri.map(str::to_string),
+        prefix: name.prefix.clone(),
+    }
+}
+
+/// Whether `name` is matched by `query`, as `local` or `prefix:local`
+fn is_named(name: &OwnedName, query: &str) -> bool {
+    match query.split_once(':') {
+        Some((prefix, local_name)) => {
+            name.prefix.as_deref() == Some(prefix) && name.local_name == local_name
+        }
+        None => name.local_name == query,
+    }
+}
+
+fn is_whitespace(text: &str) -> bool {
+    text.chars().all(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+}
@@ -0,0 +1,155 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{XFlateMode, XFlateSymbolSize, scan::Scan};
+
+/// Bytes in front of the payload: the encoding flag and backend ID,
+/// and the framing byte the deflate backend starts its output with
+const FRAME_BYTES: usize = 3;
+
+/// Projected outcome of compressing a document, computed
+/// from a scan without running any of the encoders.
+///
+/// Compressed sizes are order-0 entropy bounds. They ignore the
+/// repetitions deflate finds, so both overshoot the real output,
+/// but they are comparable with each other.
+#[derive(Debug, Clone, Copy)]
+pub struct XFlateEstimate {
+    /// Symbol size the estimate was made for
+    pub symbol_size: XFlateSymbolSize,
+    /// Size of the input XML
+    pub xml_bytes: usize,
+    /// Predicted size of the XMLN encoding
+    pub xmln_bytes: usize,
+    /// Predicted size of the XMLS encoding, excluding headers
+    pub xmls_bytes: usize,
+    /// Predicted size of the tag and symbol headers
+    pub header_bytes: usize,
+    /// Entropy of the XMLN stream, in bits per character
+    pub xmln_entropy: f64,
+    /// Entropy of the input XML, in bits per byte
+    pub xml_entropy: f64,
+    /// Projected output size using the XMLN pipeline
+    pub projected_bytes: usize,
+    /// Projected output size using the plain backend
+    pub raw_projected_bytes: usize,
+}
+
+impl XFlateEstimate {
+    /// Returns the mode expected to give the smaller output
+    pub fn mode(&self) -> XFlateMode {
+        if self.projected_bytes < self.raw_projected_bytes {
+            XFlateMode::XmlN
+        } else {
+            XFlateMode::Raw
+        }
+    }
+
+    pub(crate) fn from_scan(scan: &Scan, symbol_size: XFlateSymbolSize) -> Self {
+        let code_size = symbol_size.symbol_size as usize;
+        let mut xmln: HashMap<char, usize> = HashMap::new();
+
+        // Every token is preceded by a single whitespace
+        let tokens = scan.elements * 2 + scan.attributes * 2 + scan.texts;
+        *xmln.entry(' ').or_default() += tokens;
+        *xmln.entry('T').or_default() += scan.elements;
+        *xmln.entry('A').or_default() += scan.attributes;
+        *xmln.entry('0').or_default() += scan.elements - scan.empty_elements;
+        *xmln.entry('S').or_default() += scan.empty_elements;
+        *xmln.entry('I').or_default() += scan.indents;
+
+        // Tags and symbols are given codes in the order they are first seen
+        for (code, (_, count)) in scan.tags.iter().enumerate() {
+            for digit in code.to_string().chars() {
+                *xmln.entry(digit).or_default() += count;
+            }
+        }
+        // Indentation is written as `I` tokens rather than symbols
+        for (code, (c, count)) in scan.symbols.iter().enumerate() {
+            let count = count - scan.indented.get(c).copied().unwrap_or(0);
+            for digit in format!("{:0code_size$}", code + 1).chars() {
+                *xmln.entry(digit).or_default() += count;
+            }
+        }
+
+        // Leading whitespace is trimmed from XMLN
+        let xmln_bytes = xmln.values().sum::<usize>().saturating_sub(1);
+        let xmls_bytes = xmln_bytes.div_ceil(2);
+
+        let tag_header = 2
+            + scan.tags.len().to_string().len()
+            + 1
+            + scan
+                .tags
+                .iter()
+                .map(|(tag, _)| tag.len() + 1)
+                .sum::<usize>();
+        let symbol_header = 2
+            + code_size.to_string().len()
+            + 1
+            + scan.symbols.len().to_string().len()
+            + 1
+            + scan
+                .symbols
+                .iter()
+                .map(|(c, _)| c.len_utf8())
+                .sum::<usize>()
+            + 1;
+        let header_bytes = tag_header + symbol_header;
+
+        let xmln_entropy = entropy(xmln.values().copied());
+        let xml_entropy = entropy(scan.bytes.iter().copied());
+        let xml_bytes = scan.xml_bytes();
+
+        let projected_bytes = FRAME_BYTES + header_bytes + bits_to_bytes(xmln_bytes, xmln_entropy);
+        let raw_projected_bytes = FRAME_BYTES + bits_to_bytes(xml_bytes, xml_entropy);
+
+        XFlateEstimate {
+            symbol_size,
+            xml_bytes,
+            xmln_bytes,
+            xmls_bytes,
+            header_bytes,
+            xmln_entropy,
+            xml_entropy,
+            projected_bytes,
+            raw_projected_bytes,
+        }
+    }
+}
+
+impl Display for XFlateEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "XFlateEstimate")?;
+        write!(f, "\n  {}", self.symbol_size)?;
+        write!(f, "\n  XML bytes: {}", self.xml_bytes)?;
+        write!(f, "\n  XMLN bytes: {}", self.xmln_bytes)?;
+        write!(f, "\n  XMLS bytes: {}", self.xmls_bytes)?;
+        write!(f, "\n  Header bytes: {}", self.header_bytes)?;
+        write!(f, "\n  XMLN entropy: {:.3} bits", self.xmln_entropy)?;
+        write!(f, "\n  XML entropy: {:.3} bits", self.xml_entropy)?;
+        write!(f, "\n  Projected bytes: {}", self.projected_bytes)?;
+        write!(f, "\n  Raw projected bytes: {}", self.raw_projected_bytes)?;
+
+        Ok(())
+    }
+}
+
+/// Shannon entropy, in bits, of a distribution given by its counts
+fn entropy(counts: impl Iterator<Item = usize> + Clone) -> f64 {
+    let total: usize = counts.clone().sum();
+    if total == 0 {
+        return 0.0;
+    }
+
+    counts
+        .filter(|&count| count > 0)
+        .map(|count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn bits_to_bytes(symbols: usize, bits_per_symbol: f64) -> usize {
+    (symbols as f64 * bits_per_symbol / 8.0).ceil() as usize
+}
@@ -0,0 +1,92 @@
+use std::io::Cursor;
+
+use xml::{
+    ParserConfig,
+    reader::{Events, XmlEvent},
+};
+
+use crate::{
+    XFlateError, XmlNDynamicSymbolTable, XmlNDynamicTagTable, XmlNEvents, read_doctype,
+    verify::EventChecksum,
+};
+
+/// Events of a decompressed document, returned by
+/// `XFlate::decompress_events`.
+pub struct XFlateEvents {
+    source: Source,
+    doctype: Option<String>,
+}
+
+enum Source {
+    /// Decoded from XMLN, with the running checksum and the stored one
+    Decoded(
+        Box<XmlNEvents<XmlNDynamicSymbolTable, XmlNDynamicTagTable>>,
+        Option<(EventChecksum, u32)>,
+    ),
+    /// Parsed from the decompressed text
+    Parsed(Box<Events<Cursor<Vec<u8>>>>),
+}
+
+impl XFlateEvents {
+    pub(crate) fn decoded(
+        events: XmlNEvents<XmlNDynamicSymbolTable, XmlNDynamicTagTable>,
+        doctype: Option<String>,
+        checksum: Option<(EventChecksum, u32)>,
+    ) -> Self {
+        XFlateEvents {
+            source: Source::Decoded(Box::new(events), checksum),
+            doctype,
+        }
+    }
+
+    pub(crate) fn parse(xml: String) -> Result<Self, XFlateError> {
+        let doctype = read_doctype(xml.as_bytes())?;
+        let parser = ParserConfig::new().create_reader(Cursor::new(xml.into_bytes()));
+
+        Ok(XFlateEvents {
+            source: Source::Parsed(Box::new(parser.into_iter())),
+            doctype,
+        })
+    }
+
+    /// Document type declaration of the document, if it has one.
+    /// Parsers don't report it as an event.
+    pub fn doctype(&self) -> Option<&str> {
+        self.doctype.as_deref()
+    }
+}
+
+impl Iterator for XFlateEvents {
+    type Item = Result<XmlEvent, XFlateError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (events, checksum) = match &mut self.source {
+            Source::Decoded(events, checksum) => (events, checksum),
+            Source::Parsed(events) => {
+                return events.next().map(|e| {
+                    e.map_err(|e| XFlateError::XmlNError(format!("XML parsing error: {}", e)))
+                });
+            }
+        };
+
+        let event = match events.next()? {
+            Ok(event) => event,
+            Err(e) => return Some(Err(e)),
+        };
+
+        if let Some((running, _)) = checksum {
+            running.update(&event);
+        }
+
+        if let XmlEvent::EndDocument = event
+            && let Some((running, expected)) = checksum.take()
+        {
+            let actual = running.sum();
+            if actual != expected {
+                return Some(Err(XFlateError::ChecksumMismatch { expected, actual }));
+            }
+        }
+
+        Some(Ok(event))
+    }
+}
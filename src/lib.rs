@@ -1,9 +1,37 @@
+#[cfg(feature = "async")]
+mod async_io;
+#[cfg(feature = "serde")]
+mod de;
+mod dictionary;
+mod document;
+mod estimate;
+mod events;
+mod limits;
+mod output;
+mod scan;
+#[cfg(feature = "serde")]
+mod ser;
+mod stats;
 mod util;
+mod verify;
 mod xflate;
 mod xmlb;
 mod xmln;
 mod xmls;
 
+#[cfg(feature = "async")]
+pub use async_io::*;
+#[cfg(feature = "serde")]
+pub use de::from_slice;
+pub use dictionary::*;
+pub use document::*;
+pub use estimate::*;
+pub use events::*;
+pub use limits::*;
+pub use output::*;
+#[cfg(feature = "serde")]
+pub use ser::to_vec;
+pub use stats::*;
 pub use util::*;
 pub use xflate::*;
 pub use xmlb::*;
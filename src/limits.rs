@@ -0,0 +1,126 @@
+use crate::XFlateError;
+
+/// Room for the marker and length of a header, on top of its content
+const HEADER_OVERHEAD: usize = 32;
+
+/// Limits applied when decompressing, so that crafted or corrupt
+/// data can't make XFlate exhaust memory.
+///
+/// Exceeding any limit makes decompression fail with
+/// `XFlateError::LimitExceeded`. The defaults leave plenty of room
+/// for ordinary documents; use `unlimited` for trusted data only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XFlateLimits {
+    /// Largest decompressed XML, in bytes
+    pub max_output_bytes: usize,
+    /// Deepest nesting of elements
+    pub max_depth: usize,
+    /// Most entries in the tag table, and in the symbol table
+    pub max_table_entries: usize,
+    /// Longest tag and symbol headers, in bytes
+    pub max_header_bytes: usize,
+}
+
+impl Default for XFlateLimits {
+    fn default() -> Self {
+        XFlateLimits {
+            max_output_bytes: 256 * 1024 * 1024,
+            max_depth: 1024,
+            max_table_entries: 65536,
+            max_header_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl XFlateLimits {
+    /// No limits at all
+    pub fn unlimited() -> Self {
+        XFlateLimits {
+            max_output_bytes: usize::MAX,
+            max_depth: usize::MAX,
+            max_table_entries: usize::MAX,
+            max_header_bytes: usize::MAX,
+        }
+    }
+
+    /// Longest XMLN a document within the limits can have, with
+    /// symbol codes of `code_size` digits.
+    ///
+    /// Each output byte takes at most `code_size` digits of text and a
+    /// separator. Markup takes no more than 3 characters per byte, as
+    /// in `T65535 0` for `<a></a>`. The first indentation token also
+    /// declares the indentation, which is no longer than a header.
+    pub(crate) fn max_xmln_bytes(&self, code_size: u8) -> usize {
+        self.max_output_bytes
+            .saturating_mul((code_size as usize + 1).max(3))
+            .saturating_add(self.max_header_bytes)
+    }
+
+    /// Largest backend payload a document within the limits can have:
+    /// its XMLN packed two characters to a byte, the table and doctype
+    /// headers, and the formatting of a lossless document, which holds
+    /// no more than its output.
+    pub(crate) fn max_payload_bytes(&self, code_size: u8, lossless: bool) -> usize {
+        let formatting = if lossless {
+            self.max_output_bytes.saturating_add(HEADER_OVERHEAD)
+        } else {
+            0
+        };
+
+        self.max_xmln_bytes(code_size)
+            .div_ceil(2)
+            .saturating_add(self.max_header_bytes.saturating_mul(2))
+            .saturating_add(HEADER_OVERHEAD)
+            .saturating_add(formatting)
+    }
+
+    /// Largest compressed document within the limits: the largest
+    /// payload of any symbol code size, grown a little as backends grow
+    /// data they can't compress, and the frame in front of it.
+    #[cfg(feature = "async")]
+    pub(crate) fn max_frame_bytes(&self) -> usize {
+        let payload = self.max_payload_bytes(crate::MAX_SYMBOL_CODE_SIZE, true);
+
+        payload
+            .saturating_add(payload / 64)
+            .saturating_add(HEADER_OVERHEAD)
+    }
+
+    pub(crate) fn check_output(&self, bytes: usize) -> Result<(), XFlateError> {
+        check("Output", bytes, self.max_output_bytes)
+    }
+
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<(), XFlateError> {
+        check("Element depth", depth, self.max_depth)
+    }
+
+    pub(crate) fn check_table_entries(&self, entries: usize) -> Result<(), XFlateError> {
+        check("Table entry count", entries, self.max_table_entries)
+    }
+
+    pub(crate) fn check_header(&self, bytes: usize) -> Result<(), XFlateError> {
+        check("Header length", bytes, self.max_header_bytes)
+    }
+}
+
+/// Check the size of input gathered before running XFlate
+#[cfg(feature = "async")]
+pub(crate) fn check_input(bytes: usize, limit: usize) -> Result<(), XFlateError> {
+    check("Input", bytes, limit)
+}
+
+/// Check the size of a backend's output
+pub(crate) fn check_payload(bytes: usize, limit: usize) -> Result<(), XFlateError> {
+    check("Decompressed payload", bytes, limit)
+}
+
+fn check(what: &str, value: usize, limit: usize) -> Result<(), XFlateError> {
+    if value > limit {
+        return Err(XFlateError::LimitExceeded(format!(
+            "{} exceeds the limit of {}",
+            what, limit
+        )));
+    }
+
+    Ok(())
+}
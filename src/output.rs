@@ -0,0 +1,365 @@
+use xml::{ParserConfig, namespace::Namespace, reader::XmlEvent};
+
+use crate::{
+    XFlateError,
+    xmln::{
+        ENTITY_REFERENCE, XmlNEntities, declaration_name, declared_namespaces, parser_config,
+        preserves_space, qualified_name,
+    },
+};
+
+/// Layout of the XML returned by `XFlate::decompress_with`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum XFlateOutput {
+    /// The document as decoded, with whatever whitespace was stored
+    #[default]
+    Stored,
+    /// Whitespace between elements left out
+    Compact,
+    /// Each element on its own line, indented by `indent` per level.
+    /// Elements holding text are written as they are, so their
+    /// content is unchanged.
+    Indented { indent: String },
+    /// W3C Canonical XML 1.0, without comments. The doctype is left
+    /// out, with its entities expanded, and empty elements get an
+    /// end tag.
+    Canonical,
+}
+
+impl XFlateOutput {
+    /// Indented output with the given number of spaces per level
+    pub fn indented(spaces: usize) -> Self {
+        XFlateOutput::Indented {
+            indent: " ".repeat(spaces),
+        }
+    }
+}
+
+/// Node of the tree the output is written from
+enum Node {
+    Element(Element),
+    Text(String),
+    Comment(String),
+    Instruction(String, Option<String>),
+}
+
+struct Element {
+    name: String,
+    /// Namespaces declared on the element, as `(prefix, uri)`
+    namespaces: Vec<(String, String)>,
+    attributes: Vec<Attribute>,
+    children: Vec<Node>,
+    /// Whether whitespace must be kept, following `xml:space`
+    preserve: bool,
+}
+
+struct Attribute {
+    name: String,
+    namespace: String,
+    local_name: String,
+    value: String,
+}
+
+impl Element {
+    /// Whether the element holds other nodes, and no text besides
+    /// whitespace that may be changed
+    fn element_only(&self) -> bool {
+        !self.preserve
+            && self
+                .children
+                .iter()
+                .any(|child| !matches!(child, Node::Text(_)))
+            && self.children.iter().all(|child| match child {
+                Node::Text(text) => is_whitespace(text),
+                _ => true,
+            })
+    }
+
+    /// Children to write, leaving out whitespace that may be changed
+    fn content(&self) -> impl Iterator<Item = &Node> {
+        let element_only = self.element_only();
+
+        self.children
+            .iter()
+            .filter(move |child| !(element_only && matches!(child, Node::Text(_))))
+    }
+}
+
+/// Rewrite a document with the given layout
+pub(crate) fn format_xml(xml: &str, output: &XFlateOutput) -> Result<String, XFlateError> {
+    // References kept in the document stay as they are, except in
+    // canonical form, which expands them and leaves out comments
+    let config = match output {
+        XFlateOutput::Stored => return Ok(xml.to_string()),
+        XFlateOutput::Canonical => ParserConfig::new(),
+        _ => parser_config(xml.as_bytes(), XmlNEntities::Preserve)?.ignore_comments(false),
+    };
+    let (doctype, nodes) = parse(config, xml)?;
+
+    let mut out = String::new();
+
+    match output {
+        XFlateOutput::Stored => {}
+
+        XFlateOutput::Compact => {
+            out.extend(doctype);
+            for node in &nodes {
+                write_compact(&mut out, node);
+            }
+        }
+
+        XFlateOutput::Indented { indent } => {
+            let mut lines = doctype.into_iter().collect::<Vec<_>>();
+            lines.extend(nodes.iter().map(|node| {
+                let mut line = String::new();
+                write_indented(&mut line, node, indent, 0);
+                line
+            }));
+            out = lines.join("\n");
+        }
+
+        XFlateOutput::Canonical => {
+            let root = nodes
+                .iter()
+                .position(|node| matches!(node, Node::Element(_)))
+                .unwrap_or(nodes.len());
+
+            // Nodes around the root element are set apart by line breaks
+            for (i, node) in nodes.iter().enumerate() {
+                if i > root {
+                    out.push('\n');
+                }
+                write_canonical(&mut out, node);
+                if i < root {
+                    out.push('\n');
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Parse the document into its doctype and the nodes at the top level
+fn parse(config: ParserConfig, xml: &str) -> Result<(Option<String>, Vec<Node>), XFlateError> {
+    let mut parser = config.create_reader(xml.as_bytes());
+    let mut doctype = None;
+
+    let mut nodes = Vec::new();
+    // Open elements, with the namespaces in scope in each
+    let mut stack: Vec<(Element, Namespace)> = Vec::new();
+    let no_namespaces = Namespace::empty();
+
+    loop {
+        let e = parser
+            .next()
+            .map_err(|e| XFlateError::XmlNError(format!("XML parsing error: {}", e)))?;
+
+        let node = match e {
+            XmlEvent::EndDocument => break,
+
+            XmlEvent::StartElement {
+                name,
+                attributes,
+                namespace,
+            } => {
+                doctype = doctype.or_else(|| parser.doctype().map(str::to_string));
+
+                let parent = stack.last();
+                let namespaces = declared_namespaces(
+                    &namespace,
+                    parent.map_or(&no_namespaces, |(_, namespace)| namespace),
+                );
+                let preserve = preserves_space(
+                    &attributes,
+                    parent.is_some_and(|(element, _)| element.preserve),
+                );
+
+                let attributes = attributes
+                    .into_iter()
+                    .map(|attr| Attribute {
+                        name: qualified_name(&attr.name),
+                        namespace: attr.name.namespace.unwrap_or_default(),
+                        local_name: attr.name.local_name,
+                        value: attr.value,
+                    })
+                    .collect();
+
+                let element = Element {
+                    name: qualified_name(&name),
+                    namespaces,
+                    attributes,
+                    children: Vec::new(),
+                    preserve,
+                };
+                stack.push((element, namespace));
+                continue;
+            }
+
+            XmlEvent::EndElement { .. } => match stack.pop() {
+                Some((element, _)) => Node::Element(element),
+                None => continue,
+            },
+
+            XmlEvent::Characters(data) | XmlEvent::Whitespace(data) | XmlEvent::CData(data) => {
+                let siblings = match stack.last_mut() {
+                    Some((parent, _)) => &mut parent.children,
+                    // Only whitespace can be outside the root element
+                    None => continue,
+                };
+
+                match siblings.last_mut() {
+                    Some(Node::Text(text)) => text.push_str(&data),
+                    _ => siblings.push(Node::Text(data)),
+                }
+                continue;
+            }
+
+            XmlEvent::Comment(data) => Node::Comment(data),
+            XmlEvent::ProcessingInstruction { name, data } => Node::Instruction(name, data),
+            XmlEvent::StartDocument { .. } => continue,
+        };
+
+        match stack.last_mut() {
+            Some((parent, _)) => parent.children.push(node),
+            None => nodes.push(node),
+        }
+    }
+
+    Ok((doctype, nodes))
+}
+
+fn write_compact(out: &mut String, node: &Node) {
+    let Node::Element(element) = node else {
+        return write_other(out, node);
+    };
+
+    write_start_tag(out, element, false);
+
+    let mut content = element.content().peekable();
+    if content.peek().is_none() {
+        out.push_str("/>");
+        return;
+    }
+
+    out.push('>');
+    for child in content {
+        write_compact(out, child);
+    }
+    write_end_tag(out, element);
+}
+
+fn write_indented(out: &mut String, node: &Node, indent: &str, depth: usize) {
+    let Node::Element(element) = node else {
+        return write_other(out, node);
+    };
+
+    // Whitespace added to an element holding text would change it
+    if !element.element_only() || element.content().next().is_none() {
+        return write_compact(out, node);
+    }
+
+    write_start_tag(out, element, false);
+    out.push('>');
+    for child in element.content() {
+        out.push('\n');
+        out.push_str(&indent.repeat(depth + 1));
+        write_indented(out, child, indent, depth + 1);
+    }
+    out.push('\n');
+    out.push_str(&indent.repeat(depth));
+    write_end_tag(out, element);
+}
+
+fn write_canonical(out: &mut String, node: &Node) {
+    let Node::Element(element) = node else {
+        return write_other(out, node);
+    };
+
+    write_start_tag(out, element, true);
+    out.push('>');
+    for child in &element.children {
+        write_canonical(out, child);
+    }
+    write_end_tag(out, element);
+}
+
+/// Write the start tag, without the closing '>'. In canonical form,
+/// namespace declarations are sorted by prefix, followed by the
+/// attributes sorted by namespace and local name.
+fn write_start_tag(out: &mut String, element: &Element, canonical: bool) {
+    out.push('<');
+    out.push_str(&element.name);
+
+    for (prefix, uri) in &element.namespaces {
+        write_attribute(out, &declaration_name(prefix), uri);
+    }
+
+    let mut attributes = element.attributes.iter().collect::<Vec<_>>();
+    if canonical {
+        attributes
+            .sort_by(|a, b| (&a.namespace, &a.local_name).cmp(&(&b.namespace, &b.local_name)));
+    }
+
+    for attr in attributes {
+        write_attribute(out, &attr.name, &attr.value);
+    }
+}
+
+fn write_end_tag(out: &mut String, element: &Element) {
+    out.push_str("</");
+    out.push_str(&element.name);
+    out.push('>');
+}
+
+fn write_attribute(out: &mut String, name: &str, value: &str) {
+    out.push(' ');
+    out.push_str(name);
+    out.push_str("=\"");
+    escape(out, value, true);
+    out.push('"');
+}
+
+/// Write a node other than an element
+fn write_other(out: &mut String, node: &Node) {
+    match node {
+        Node::Element(_) => {}
+        Node::Text(text) => escape(out, text, false),
+        Node::Comment(text) => {
+            out.push_str("<!--");
+            out.push_str(text);
+            out.push_str("-->");
+        }
+        Node::Instruction(name, data) => {
+            out.push_str("<?");
+            out.push_str(name);
+            if let Some(data) = data {
+                out.push(' ');
+                out.push_str(data);
+            }
+            out.push_str("?>");
+        }
+    }
+}
+
+/// Escape text, or an attribute value if `attribute`, as in
+/// canonical form. Entity references kept in the text are restored.
+fn escape(out: &mut String, text: &str, attribute: bool) {
+    for c in text.chars() {
+        match c {
+            ENTITY_REFERENCE => out.push('&'),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' if !attribute => out.push_str("&gt;"),
+            '"' if attribute => out.push_str("&quot;"),
+            '\t' if attribute => out.push_str("&#x9;"),
+            '\n' if attribute => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn is_whitespace(text: &str) -> bool {
+    text.chars().all(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::HashMap,
+    io::{BufReader, Read},
+};
+use xml::{ParserConfig, namespace::Namespace, reader::XmlEvent};
+
+use crate::{
+    XFlateError,
+    xmln::{Indentation, declaration_name, declared_namespaces, qualified_name},
+};
+
+/// Result of a scan over an XML document.
+///
+/// Characters and tags are kept in the order they were first
+/// seen, which is the order the dynamic tables assign codes in.
+#[derive(Debug, Default)]
+pub(crate) struct Scan {
+    /// Unique characters and their number of occurrences
+    pub symbols: Vec<(char, usize)>,
+    /// Unique tags and attribute names and their number of occurrences
+    pub tags: Vec<(String, usize)>,
+    /// Number of elements
+    pub elements: usize,
+    /// Number of elements without content, closed by a single token
+    pub empty_elements: usize,
+    /// Number of attributes
+    pub attributes: usize,
+    /// Number of character and whitespace runs
+    pub texts: usize,
+    /// Number of whitespace runs matching the indentation of the
+    /// document, which the encoder writes as `I` tokens
+    pub indents: usize,
+    /// Characters of those runs, which take no symbol codes.
+    /// The declaration after the first `I` token is left out.
+    pub indented: HashMap<char, usize>,
+    /// Number of occurrences of each byte in the document
+    pub bytes: Vec<usize>,
+}
+
+impl Scan {
+    pub fn unique_symbols(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn xml_bytes(&self) -> usize {
+        self.bytes.iter().sum()
+    }
+}
+
+/// Run a scan over the XML data.
+/// This counts symbols, tags and bytes in the XML data.
+///
+/// # Warning
+/// There is no guarantee that a scan performed on one document,
+/// will yield a result that will work for another document.
+pub(crate) fn scan<D>(xml: D) -> Result<Scan, XFlateError>
+where
+    D: Read,
+{
+    let mut bytes = vec![0; 256];
+    let buf = BufReader::new(ByteCounter {
+        inner: xml,
+        counts: &mut bytes,
+    });
+    let config = ParserConfig::new();
+    let parser = config.create_reader(buf);
+
+    let events = parser
+        .into_iter()
+        .map(|e| e.map_err(|e| XFlateError::PrePassError(format!("XML parsing error: {}", e))));
+    let mut scan = scan_events(events)?;
+    scan.bytes = bytes;
+
+    Ok(scan)
+}
+
+/// Run a scan over the events of a document.
+/// Bytes are left uncounted, as there is no text.
+pub(crate) fn scan_events<I>(events: I) -> Result<Scan, XFlateError>
+where
+    I: IntoIterator<Item = Result<XmlEvent, XFlateError>>,
+{
+    let mut scan = Scan::default();
+    let mut symbols: HashMap<char, usize> = HashMap::new();
+    let mut tags: HashMap<String, usize> = HashMap::new();
+    // Whether the last element started has no content so far
+    let mut empty = false;
+    // Namespaces in scope in each open element
+    let mut namespaces: Vec<Namespace> = Vec::new();
+    let no_namespaces = Namespace::empty();
+    // Whitespace waits for the next token, as in the encoder
+    let mut pending: Option<String> = None;
+    let mut indentation: Option<Indentation> = None;
+
+    for e in events {
+        let makes_token = match &e {
+            Ok(XmlEvent::StartElement { .. }) | Ok(XmlEvent::EndElement { .. }) => true,
+            Ok(XmlEvent::Characters(data)) | Ok(XmlEvent::Whitespace(data)) => !data.is_empty(),
+            _ => false,
+        };
+
+        if let Some(whitespace) = pending.take_if(|_| makes_token) {
+            // A closing token ends the element the whitespace is in
+            let depth = match &e {
+                Ok(XmlEvent::EndElement { .. }) => namespaces.len().saturating_sub(1),
+                _ => namespaces.len(),
+            };
+
+            count_indent(&mut scan, &mut indentation, &whitespace, depth);
+        }
+
+        match e {
+            Ok(XmlEvent::Characters(data)) => {
+                if data.is_empty() {
+                    continue;
+                }
+
+                scan.texts += 1;
+                count_symbols(&mut scan, &mut symbols, &data);
+                empty = false;
+            }
+            Ok(XmlEvent::Whitespace(data)) => {
+                if data.is_empty() {
+                    continue;
+                }
+
+                scan.texts += 1;
+                count_symbols(&mut scan, &mut symbols, &data);
+                pending = Some(data);
+                empty = false;
+            }
+            Ok(XmlEvent::EndElement { name: _ }) => {
+                namespaces.pop();
+                if empty {
+                    scan.empty_elements += 1;
+                }
+                empty = false;
+            }
+            Ok(XmlEvent::StartElement {
+                name,
+                attributes,
+                namespace,
+            }) => {
+                scan.elements += 1;
+                count_tag(&mut scan, &mut tags, qualified_name(&name));
+                empty = true;
+
+                // The encoder writes namespace declarations as attributes
+                let parent = namespaces.last().unwrap_or(&no_namespaces);
+                for (prefix, uri) in declared_namespaces(&namespace, parent) {
+                    scan.attributes += 1;
+                    count_tag(&mut scan, &mut tags, declaration_name(&prefix));
+                    count_symbols(&mut scan, &mut symbols, &uri);
+                }
+                namespaces.push(namespace);
+
+                for attr in attributes {
+                    scan.attributes += 1;
+                    count_tag(&mut scan, &mut tags, qualified_name(&attr.name));
+                    count_symbols(&mut scan, &mut symbols, &attr.value);
+                }
+            }
+            Ok(XmlEvent::CData(_)) => {
+                return Err(XFlateError::PrePassError(
+                    "CData not implemented yet".to_string(),
+                ));
+            }
+            Ok(XmlEvent::Comment(_)) => {
+                return Err(XFlateError::PrePassError(
+                    "Comment not implemented yet".to_string(),
+                ));
+            }
+            Err(e) => return Err(e),
+            _ => continue,
+        };
+    }
+
+    for (c, count) in scan.symbols.iter_mut() {
+        *count = symbols[c];
+    }
+    for (tag, count) in scan.tags.iter_mut() {
+        *count = tags[tag];
+    }
+
+    Ok(scan)
+}
+
+fn count_symbols(scan: &mut Scan, symbols: &mut HashMap<char, usize>, data: &str) {
+    for c in data.chars() {
+        let count = symbols.entry(c).or_insert_with(|| {
+            scan.symbols.push((c, 0));
+            0
+        });
+        *count += 1;
+    }
+}
+
+/// Count whitespace found at `depth` as an indentation token, if
+/// it matches the indentation of the document as the encoder sees it
+fn count_indent(
+    scan: &mut Scan,
+    indentation: &mut Option<Indentation>,
+    whitespace: &str,
+    depth: usize,
+) {
+    let declaration = match indentation {
+        Some(indentation) if indentation.at(depth) == whitespace => String::new(),
+        Some(_) => return,
+        None => match Indentation::detect(whitespace, depth) {
+            Some(detected) => indentation.insert(detected).declaration(),
+            None => return,
+        },
+    };
+
+    scan.indents += 1;
+    for c in whitespace.chars() {
+        *scan.indented.entry(c).or_default() += 1;
+    }
+    for c in declaration.chars() {
+        *scan.indented.entry(c).or_default() -= 1;
+    }
+}
+
+fn count_tag(scan: &mut Scan, tags: &mut HashMap<String, usize>, tag: String) {
+    let count = tags.entry(tag).or_insert_with_key(|tag| {
+        scan.tags.push((tag.clone(), 0));
+        0
+    });
+    *count += 1;
+}
+
+/// Reader counting the occurrences of every byte read through it.
+struct ByteCounter<'a, R> {
+    inner: R,
+    counts: &'a mut [usize],
+}
+
+impl<R: Read> Read for ByteCounter<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        for &b in &buf[..n] {
+            self.counts[b as usize] += 1;
+        }
+        Ok(n)
+    }
+}
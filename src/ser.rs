@@ -0,0 +1,492 @@
+use serde::{Serialize, ser};
+use xml::{
+    attribute::OwnedAttribute,
+    common::{is_name_char, is_name_start_char},
+    name::OwnedName,
+    namespace::Namespace,
+    reader::XmlEvent,
+};
+
+use crate::{XFlate, XFlateConfig, XFlateError};
+
+/// Name of the elements holding the items of a sequence or tuple
+pub(crate) const ITEM: &str = "item";
+/// Name of the elements holding the entries of a map
+pub(crate) const ENTRY: &str = "entry";
+pub(crate) const KEY: &str = "key";
+pub(crate) const VALUE: &str = "value";
+/// Attribute marking an element that holds `None`
+pub(crate) const NIL: &str = "nil";
+
+/// Serialize a value into the XFlate format, with the default config.
+///
+/// The value is written as the events of a document and compressed
+/// with `XFlate::compress_events`, so field names go to the tag table
+/// and scalars to the symbol table without any text being written.
+///
+/// Each value becomes an element named after its field, holding
+/// either text or the elements of its own fields. The root element
+/// is named after the type. Sequence items are `item` elements, map
+/// entries are `entry` elements holding a `key` and a `value`, and
+/// enum variants other than unit variants are wrapped in an element
+/// named after the variant. A `None` field or map value leaves the
+/// element out, while other `None` values are written as an empty
+/// element with a `nil="true"` attribute.
+///
+/// Names must be valid XML names without a colon, or an error is
+/// returned.
+pub fn to_vec<V>(value: &V) -> Result<Vec<u8>, XFlateError>
+where
+    V: Serialize + ?Sized,
+{
+    let mut events = Vec::new();
+    value.serialize(Serializer {
+        events: &mut events,
+        name: None,
+        optional: false,
+    })?;
+
+    XFlate::new(XFlateConfig::default()).compress_events(events)
+}
+
+impl ser::Error for XFlateError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        XFlateError::SerdeError(msg.to_string())
+    }
+}
+
+/// Writes a value as an element named `name`, or after its type
+/// at the root of the document
+struct Serializer<'a> {
+    events: &'a mut Vec<XmlEvent>,
+    name: Option<&'a str>,
+    /// Whether a missing element reads back as `None`
+    optional: bool,
+}
+
+impl<'a> Serializer<'a> {
+    fn name(&self, type_name: &'a str) -> &'a str {
+        self.name.unwrap_or(type_name)
+    }
+
+    /// Writes to another element, keeping whether it is optional
+    fn renamed(self, name: &'a str) -> Serializer<'a> {
+        Serializer {
+            events: self.events,
+            name: Some(name),
+            optional: self.optional,
+        }
+    }
+
+    fn text(self, text: String) -> Result<(), XFlateError> {
+        let name = self.name(VALUE);
+        start(self.events, name)?;
+        if !text.is_empty() {
+            self.events.push(XmlEvent::Characters(text));
+        }
+        end(self.events, name);
+
+        Ok(())
+    }
+
+    fn empty(self, type_name: &'a str) -> Result<(), XFlateError> {
+        let name = self.name(type_name);
+        start(self.events, name)?;
+        end(self.events, name);
+
+        Ok(())
+    }
+
+    fn nil(self) -> Result<(), XFlateError> {
+        let name = self.name(VALUE);
+        check_name(name)?;
+        self.events.push(XmlEvent::StartElement {
+            name: OwnedName::local(name),
+            attributes: vec![OwnedAttribute::new(OwnedName::local(NIL), "true")],
+            namespace: Namespace::empty(),
+        });
+        end(self.events, name);
+
+        Ok(())
+    }
+
+    /// Start an element holding others, closed by `Compound`.
+    /// A variant gets its own element inside it.
+    fn compound(
+        self,
+        type_name: &'a str,
+        variant: Option<&'a str>,
+    ) -> Result<Compound<'a>, XFlateError> {
+        let mut names = vec![self.name(type_name)];
+        names.extend(variant);
+
+        for name in &names {
+            start(self.events, name)?;
+        }
+
+        Ok(Compound {
+            events: self.events,
+            names,
+        })
+    }
+}
+
+fn start(events: &mut Vec<XmlEvent>, name: &str) -> Result<(), XFlateError> {
+    check_name(name)?;
+    events.push(XmlEvent::StartElement {
+        name: OwnedName::local(name),
+        attributes: Vec::new(),
+        namespace: Namespace::empty(),
+    });
+
+    Ok(())
+}
+
+/// Names become element names, so they must be valid XML names.
+/// A colon would be read as a namespace prefix.
+fn check_name(name: &str) -> Result<(), XFlateError> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(is_name_start_char)
+        && chars.all(is_name_char)
+        && !name.contains(':');
+
+    if !valid {
+        return Err(XFlateError::SerdeError(format!(
+            "Name {:?} is not a valid XML element name",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+fn end(events: &mut Vec<XmlEvent>, name: &str) {
+    events.push(XmlEvent::EndElement {
+        name: OwnedName::local(name),
+    });
+}
+
+macro_rules! serialize_display {
+    ($($method:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method(self, value: $ty) -> Result<(), XFlateError> {
+                self.text(value.to_string())
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for Serializer<'a> {
+    type Ok = ();
+    type Error = XFlateError;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    serialize_display! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), XFlateError> {
+        use ser::SerializeSeq;
+
+        let mut seq = self.serialize_seq(Some(value.len()))?;
+        for byte in value {
+            seq.serialize_element(byte)?;
+        }
+        seq.end()
+    }
+
+    fn serialize_none(self) -> Result<(), XFlateError> {
+        if self.optional {
+            return Ok(());
+        }
+
+        self.nil()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), XFlateError> {
+        self.empty(VALUE)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), XFlateError> {
+        self.empty(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), XFlateError> {
+        let name = self.name(name);
+        self.renamed(name).text(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let name = self.name(name);
+        value.serialize(self.renamed(name))
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let name = self.name(name);
+        start(self.events, name)?;
+        value.serialize(Serializer {
+            events: self.events,
+            name: Some(variant),
+            optional: false,
+        })?;
+        end(self.events, name);
+
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, XFlateError> {
+        self.compound(VALUE, None)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, XFlateError> {
+        self.compound(VALUE, None)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, XFlateError> {
+        self.compound(name, None)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, XFlateError> {
+        self.compound(name, Some(variant))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, XFlateError> {
+        self.compound(VALUE, None)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, XFlateError> {
+        self.compound(name, None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, XFlateError> {
+        self.compound(name, Some(variant))
+    }
+}
+
+/// Element holding items, entries or fields, with the
+/// names of the elements to close at the end
+struct Compound<'a> {
+    events: &'a mut Vec<XmlEvent>,
+    names: Vec<&'a str>,
+}
+
+impl Compound<'_> {
+    /// Write a value, which must be read back even if `None`
+    fn element<T>(&mut self, name: &str, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(Serializer {
+            events: self.events,
+            name: Some(name),
+            optional: false,
+        })
+    }
+
+    /// Write a value whose element is left out if it is `None`
+    fn field<T>(&mut self, name: &str, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(Serializer {
+            events: self.events,
+            name: Some(name),
+            optional: true,
+        })
+    }
+
+    fn end(self) -> Result<(), XFlateError> {
+        for name in self.names.iter().rev() {
+            end(self.events, name);
+        }
+
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = XFlateError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(ITEM, value)
+    }
+
+    fn end(self) -> Result<(), XFlateError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = XFlateError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(ITEM, value)
+    }
+
+    fn end(self) -> Result<(), XFlateError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = XFlateError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(ITEM, value)
+    }
+
+    fn end(self) -> Result<(), XFlateError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = XFlateError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(ITEM, value)
+    }
+
+    fn end(self) -> Result<(), XFlateError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = XFlateError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        start(self.events, ENTRY)?;
+        self.element(KEY, key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(VALUE, value)?;
+        end(self.events, ENTRY);
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), XFlateError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = XFlateError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), XFlateError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = XFlateError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), XFlateError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), XFlateError> {
+        Compound::end(self)
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::HashMap, fmt::Display, time::Duration};
+
+use crate::{XFlateEncoding, XFlateError, XmlNSymbolTable, XmlNTagTable, split_codes};
+
+/// Number of most frequent tags and characters kept in `XFlateStats`.
+pub const XFLATE_STATS_TOP_COUNT: usize = 10;
+
+/// Statistics collected by `XFlate::compress_with_stats`
+/// or `XFlate::inspect`.
+///
+/// Byte counts and durations for stages that didn't
+/// run for the chosen mode are left at zero. Durations
+/// are spent encoding or decoding, respectively.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct XFlateStats {
+    /// Encoding stored in the compressed output
+    pub encoding: XFlateEncoding,
+
+    /// Size of the input XML
+    pub xml_bytes: usize,
+    /// Size of the XMLN encoding
+    pub xmln_bytes: usize,
+    /// Size of the XMLS encoding, excluding headers
+    pub xmls_bytes: usize,
+    /// Size of the tag table header
+    pub tag_header_bytes: usize,
+    /// Size of the symbol table header
+    pub symbol_header_bytes: usize,
+    /// Size of the formatting header, for the lossless encoding
+    pub formatting_bytes: usize,
+    /// Size of the doctype header
+    pub doctype_bytes: usize,
+    /// Size of the backend output for XMLS and headers
+    pub xmlb_bytes: usize,
+    /// Size of the backend output for the plain XML
+    pub raw_bytes: usize,
+    /// Size of the final compressed output
+    pub output_bytes: usize,
+
+    /// Number of tags and attribute names in the tag table
+    pub tag_count: usize,
+    /// Number of characters in the symbol table
+    pub symbol_count: usize,
+    /// Size of symbol codes
+    pub code_size: u8,
+
+    /// Time spent in the XMLN stage
+    pub xmln_time: Duration,
+    /// Time spent in the XMLS stage
+    pub xmls_time: Duration,
+    /// Time spent in the backend on XMLS
+    pub xmlb_time: Duration,
+    /// Time spent in the backend on the plain XML
+    pub raw_time: Duration,
+
+    /// Most frequent tags and attribute names, most frequent first
+    pub top_tags: Vec<XFlateFrequency<String>>,
+    /// Most frequent characters, most frequent first
+    pub top_symbols: Vec<XFlateFrequency<char>>,
+}
+
+/// Number of occurrences of a value in a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct XFlateFrequency<T> {
+    pub value: T,
+    pub count: usize,
+}
+
+type Frequencies<T> = Vec<XFlateFrequency<T>>;
+
+impl XFlateStats {
+    /// Compressed output size relative to the input size
+    pub fn ratio(&self) -> f64 {
+        if self.xml_bytes == 0 {
+            return 0.0;
+        }
+
+        self.output_bytes as f64 / self.xml_bytes as f64
+    }
+}
+
+impl Display for XFlateStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "XFlateStats")?;
+        write!(f, "\n  Encoding: {:?}", self.encoding)?;
+        write!(f, "\n  XML bytes: {}", self.xml_bytes)?;
+        write!(
+            f,
+            "\n  XMLN bytes: {} ({:?})",
+            self.xmln_bytes, self.xmln_time
+        )?;
+        write!(
+            f,
+            "\n  XMLS bytes: {} ({:?})",
+            self.xmls_bytes, self.xmls_time
+        )?;
+        write!(f, "\n  Tag header bytes: {}", self.tag_header_bytes)?;
+        write!(f, "\n  Symbol header bytes: {}", self.symbol_header_bytes)?;
+        write!(f, "\n  Formatting bytes: {}", self.formatting_bytes)?;
+        write!(f, "\n  Doctype bytes: {}", self.doctype_bytes)?;
+        write!(
+            f,
+            "\n  XMLB bytes: {} ({:?})",
+            self.xmlb_bytes, self.xmlb_time
+        )?;
+        write!(f, "\n  Raw bytes: {} ({:?})", self.raw_bytes, self.raw_time)?;
+        write!(f, "\n  Output bytes: {}", self.output_bytes)?;
+        write!(f, "\n  Ratio: {:.2}%", self.ratio() * 100.0)?;
+        write!(f, "\n  Tags: {}", self.tag_count)?;
+        write!(
+            f,
+            "\n  Symbols: {} (code size {})",
+            self.symbol_count, self.code_size
+        )?;
+        write!(f, "\n  Top tags:")?;
+        for freq in &self.top_tags {
+            write!(f, "\n    {} x{}", freq.value, freq.count)?;
+        }
+        write!(f, "\n  Top symbols:")?;
+        for freq in &self.top_symbols {
+            write!(f, "\n    {:?} x{}", freq.value, freq.count)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Count tag and symbol occurrences in an XMLN encoding, and
+/// return the most frequent of each.
+pub(crate) fn top_frequencies<S, T>(
+    xmln: &str,
+    sym_table: &S,
+    tag_table: &T,
+) -> Result<(Frequencies<String>, Frequencies<char>), XFlateError>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    let mut tags: HashMap<&str, usize> = HashMap::new();
+    let mut symbols: HashMap<char, usize> = HashMap::new();
+
+    for token in xmln.split(' ') {
+        match token.as_bytes().first() {
+            None => continue,
+            Some(b'T' | b'A') => {
+                let tag = token[1..]
+                    .parse()
+                    .ok()
+                    .and_then(|code| tag_table.decode(code))
+                    .ok_or_else(|| XFlateError::XmlNError(format!("Unknown tag: {}", token)))?;
+                *tags.entry(tag).or_default() += 1;
+            }
+            // Indentation is only counted as the token
+            Some(b'I') => continue,
+            Some(_) if token == "0" || token == "S" => continue,
+            Some(_) => {
+                for code in split_codes(token, sym_table.code_size())? {
+                    let symbol = sym_table.decode(code).ok_or_else(|| {
+                        XFlateError::XmlNError(format!("Unknown symbol code: {}", code))
+                    })?;
+                    *symbols.entry(symbol).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let top_tags = top(tags.into_iter().map(|(tag, count)| XFlateFrequency {
+        value: tag.to_string(),
+        count,
+    }));
+    let top_symbols = top(symbols
+        .into_iter()
+        .map(|(value, count)| XFlateFrequency { value, count }));
+
+    Ok((top_tags, top_symbols))
+}
+
+fn top<T: Ord>(freqs: impl Iterator<Item = XFlateFrequency<T>>) -> Frequencies<T> {
+    let mut freqs: Vec<_> = freqs.collect();
+    freqs.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    freqs.truncate(XFLATE_STATS_TOP_COUNT);
+    freqs
+}
@@ -0,0 +1,373 @@
+use std::{collections::HashMap, convert::Infallible, fmt::Display};
+
+use flate2::Crc;
+use xml::{ParserConfig, reader::XmlEvent};
+
+use crate::{XFlateError, xmln::preserves_space};
+
+/// Event of an XML document, reduced to what XFlate preserves.
+/// Adjacent character and whitespace runs form a single text.
+#[derive(Debug, PartialEq)]
+enum CanonicalEvent {
+    Start {
+        name: String,
+        attributes: Vec<(String, String)>,
+    },
+    End,
+    Text(String),
+}
+
+/// Check that `actual` holds the same canonical events as `expected`.
+/// With `strip_whitespace`, whitespace the encoder may leave out is
+/// ignored, as in `XmlNOptions`.
+///
+/// On a difference, the error names the path of the first element
+/// that diverges, such as `/section[1]/para[2]`, where the index
+/// counts siblings of the same name.
+pub(crate) fn verify(
+    expected: &[u8],
+    actual: &[u8],
+    strip_whitespace: bool,
+) -> Result<(), XFlateError> {
+    if expected == actual {
+        return Ok(());
+    }
+
+    let (expected, error) = canonical_events(expected, strip_whitespace);
+    if let Some(error) = error {
+        return Err(XFlateError::VerificationError(format!(
+            "Input is not well-formed XML: {}",
+            error
+        )));
+    }
+
+    compare(&expected, actual, strip_whitespace)
+}
+
+/// Check that `actual` holds the same canonical events as the
+/// `expected` events, as with `verify`.
+pub(crate) fn verify_events(
+    expected: &[XmlEvent],
+    actual: &[u8],
+    strip_whitespace: bool,
+) -> Result<(), XFlateError> {
+    let (expected, _) = canonicalize(
+        expected.iter().cloned().map(Ok::<_, Infallible>),
+        strip_whitespace,
+    );
+
+    compare(&expected, actual, strip_whitespace)
+}
+
+fn compare(
+    expected: &[CanonicalEvent],
+    actual: &[u8],
+    strip_whitespace: bool,
+) -> Result<(), XFlateError> {
+    let (actual, actual_error) = canonical_events(actual, strip_whitespace);
+
+    let mut path = ElementPath::new();
+    for (i, event) in expected.iter().enumerate() {
+        let found = actual.get(i);
+
+        if found != Some(event) {
+            if let CanonicalEvent::Start { name, .. } = event {
+                path.enter(name);
+            }
+
+            let found = match (found, &actual_error) {
+                (Some(found), _) => describe(found),
+                (None, Some(error)) => format!("malformed XML ({})", error),
+                (None, None) => "the end of the document".to_string(),
+            };
+
+            return Err(XFlateError::VerificationError(format!(
+                "Output differs at {}: expected {}, found {}",
+                path,
+                describe(event),
+                found
+            )));
+        }
+
+        match event {
+            CanonicalEvent::Start { name, .. } => path.enter(name),
+            CanonicalEvent::End => path.leave(),
+            CanonicalEvent::Text(_) => {}
+        }
+    }
+
+    if let Some(extra) = actual.get(expected.len()) {
+        return Err(XFlateError::VerificationError(format!(
+            "Output differs at {}: expected the end of the document, found {}",
+            path,
+            describe(extra)
+        )));
+    }
+
+    if let Some(error) = actual_error {
+        return Err(XFlateError::VerificationError(format!(
+            "Output is not well-formed XML: {}",
+            error
+        )));
+    }
+
+    Ok(())
+}
+
+/// CRC-32 of the canonical events of a document. Unlike a checksum
+/// of the bytes, this doesn't depend on what XFlate leaves out, such
+/// as the XML declaration. Malformed XML is hashed up to the error.
+pub(crate) fn event_checksum(xml: &[u8], strip_whitespace: bool) -> u32 {
+    let (events, _) = canonical_events(xml, strip_whitespace);
+    checksum(&events)
+}
+
+/// Checksum of the events of a document, matching the `event_checksum`
+/// of the XML they describe
+pub(crate) fn events_checksum(events: &[XmlEvent], strip_whitespace: bool) -> u32 {
+    let mut checksum = EventChecksum::new(strip_whitespace);
+    for event in events {
+        checksum.update(event);
+    }
+    checksum.sum()
+}
+
+/// Running `event_checksum` over events read one at a time
+pub(crate) struct EventChecksum {
+    canonicalizer: Canonicalizer,
+    crc: Crc,
+    /// Canonical events completed by the last update
+    completed: Vec<CanonicalEvent>,
+}
+
+impl EventChecksum {
+    pub fn new(strip_whitespace: bool) -> Self {
+        EventChecksum {
+            canonicalizer: Canonicalizer::new(strip_whitespace),
+            crc: Crc::new(),
+            completed: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, event: &XmlEvent) {
+        self.canonicalizer.push(event, &mut self.completed);
+        self.hash_completed();
+    }
+
+    pub fn sum(mut self) -> u32 {
+        self.canonicalizer.finish(&mut self.completed);
+        self.hash_completed();
+        self.crc.sum()
+    }
+
+    fn hash_completed(&mut self) {
+        for event in self.completed.drain(..) {
+            hash(&mut self.crc, &event);
+        }
+    }
+}
+
+fn checksum(events: &[CanonicalEvent]) -> u32 {
+    let mut crc = Crc::new();
+    for event in events {
+        hash(&mut crc, event);
+    }
+    crc.sum()
+}
+
+fn hash(crc: &mut Crc, event: &CanonicalEvent) {
+    match event {
+        CanonicalEvent::Start { name, attributes } => {
+            crc.update(b"S");
+            put(crc, name);
+            crc.update(&(attributes.len() as u64).to_le_bytes());
+            for (name, value) in attributes {
+                put(crc, name);
+                put(crc, value);
+            }
+        }
+        CanonicalEvent::End => crc.update(b"E"),
+        CanonicalEvent::Text(text) => {
+            crc.update(b"T");
+            put(crc, text);
+        }
+    }
+}
+
+/// Strings are length prefixed, so events can't run into each other
+fn put(crc: &mut Crc, s: &str) {
+    crc.update(&(s.len() as u64).to_le_bytes());
+    crc.update(s.as_bytes());
+}
+
+/// Parse the document into canonical events. Parsing stops at the
+/// first error, which is returned along with the events before it.
+/// With `strip_whitespace`, whitespace outside of elements with
+/// `xml:space="preserve"` is left out.
+fn canonical_events(xml: &[u8], strip_whitespace: bool) -> (Vec<CanonicalEvent>, Option<String>) {
+    let parser = ParserConfig::new().create_reader(xml);
+    canonicalize(parser, strip_whitespace)
+}
+
+/// Reduce events to canonical events, as `canonical_events` does
+fn canonicalize<I, E>(input: I, strip_whitespace: bool) -> (Vec<CanonicalEvent>, Option<String>)
+where
+    I: IntoIterator<Item = Result<XmlEvent, E>>,
+    E: Display,
+{
+    let mut canonicalizer = Canonicalizer::new(strip_whitespace);
+    let mut events = Vec::new();
+
+    for e in input {
+        match e {
+            Ok(event) => canonicalizer.push(&event, &mut events),
+            Err(e) => {
+                canonicalizer.finish(&mut events);
+                return (events, Some(e.to_string()));
+            }
+        }
+    }
+
+    canonicalizer.finish(&mut events);
+    (events, None)
+}
+
+/// Reduces events to canonical events, one at a time. Text is held
+/// back until the next event, as adjacent runs form a single text.
+struct Canonicalizer {
+    strip_whitespace: bool,
+    /// Whether each open element preserves whitespace
+    preserve: Vec<bool>,
+    text: Option<String>,
+}
+
+impl Canonicalizer {
+    fn new(strip_whitespace: bool) -> Self {
+        Canonicalizer {
+            strip_whitespace,
+            preserve: Vec::new(),
+            text: None,
+        }
+    }
+
+    /// Add the canonical events completed by `event` to `out`
+    fn push(&mut self, event: &XmlEvent, out: &mut Vec<CanonicalEvent>) {
+        match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                self.finish(out);
+                self.preserve.push(preserves_space(
+                    attributes,
+                    self.preserve.last().copied().unwrap_or(false),
+                ));
+
+                let attributes = attributes
+                    .iter()
+                    .map(|attr| (attr.name.to_string(), attr.value.clone()))
+                    .collect();
+
+                out.push(CanonicalEvent::Start {
+                    name: name.to_string(),
+                    attributes,
+                });
+            }
+
+            XmlEvent::EndElement { .. } => {
+                self.finish(out);
+                self.preserve.pop();
+                out.push(CanonicalEvent::End);
+            }
+
+            XmlEvent::Whitespace(_)
+                if self.strip_whitespace && self.preserve.last() != Some(&true) => {}
+
+            // Generated events may hold empty text, which a parser never reports
+            XmlEvent::Characters(data) | XmlEvent::Whitespace(data) | XmlEvent::CData(data)
+                if !data.is_empty() =>
+            {
+                self.text.get_or_insert_default().push_str(data);
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Add any text held back to `out`
+    fn finish(&mut self, out: &mut Vec<CanonicalEvent>) {
+        if let Some(text) = self.text.take() {
+            out.push(CanonicalEvent::Text(text));
+        }
+    }
+}
+
+fn describe(event: &CanonicalEvent) -> String {
+    match event {
+        CanonicalEvent::Start { name, attributes } if attributes.is_empty() => {
+            format!("element {}", name)
+        }
+        CanonicalEvent::Start { name, attributes } => {
+            let attributes = attributes
+                .iter()
+                .map(|(name, value)| format!("{}={:?}", name, value))
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("element {} with {}", name, attributes)
+        }
+        CanonicalEvent::End => "the end of the element".to_string(),
+        CanonicalEvent::Text(text) if text.chars().count() > 40 => {
+            format!("text {:?}...", text.chars().take(40).collect::<String>())
+        }
+        CanonicalEvent::Text(text) => format!("text {:?}", text),
+    }
+}
+
+/// Path to the current element, with sibling indices
+struct ElementPath {
+    names: Vec<String>,
+    /// Number of children seen so far with each name, per level
+    siblings: Vec<HashMap<String, usize>>,
+}
+
+impl ElementPath {
+    fn new() -> Self {
+        ElementPath {
+            names: Vec::new(),
+            siblings: vec![HashMap::new()],
+        }
+    }
+
+    fn enter(&mut self, name: &str) {
+        let index = self
+            .siblings
+            .last_mut()
+            .map(|siblings| {
+                let count = siblings.entry(name.to_string()).or_default();
+                *count += 1;
+                *count
+            })
+            .unwrap_or(1);
+
+        self.names.push(format!("{}[{}]", name, index));
+        self.siblings.push(HashMap::new());
+    }
+
+    fn leave(&mut self) {
+        self.names.pop();
+        self.siblings.pop();
+    }
+}
+
+impl Display for ElementPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.names.is_empty() {
+            return write!(f, "/");
+        }
+
+        for name in &self.names {
+            write!(f, "/{}", name)?;
+        }
+
+        Ok(())
+    }
+}
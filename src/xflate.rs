@@ -1,17 +1,34 @@
-use core::panic;
-use itertools::{Itertools, MultiPeek};
+use flate2::Crc;
+use itertools::Itertools;
 use std::{
     collections::HashSet,
-    fmt::format,
-    io::{BufReader, Read},
-    str::Chars,
+    fmt::Display,
+    io::Read,
+    time::{Duration, Instant},
 };
-use xml::{ParserConfig, reader::XmlEvent};
+use xml::reader::XmlEvent;
 
+#[cfg(feature = "brotli")]
+use crate::XmlBBrotliBackend;
+#[cfg(feature = "lzma")]
+use crate::XmlBLzmaBackend;
+#[cfg(feature = "zstd")]
+use crate::XmlBZstdBackend;
+#[cfg(feature = "async")]
+use crate::decode_xmln_streamed;
 use crate::{
-    XmlBCompress, XmlBDecompressor, XmlBDeflateBackend, XmlNDynamicSymbolTable,
-    XmlNDynamicTagTable, XmlNSymbolTable, XmlNTagTable, consume_until_space, decode_xmlb,
-    decode_xmln, decode_xmls, encode_xmlb, encode_xmln, encode_xmls,
+    MAX_SYMBOL_CODE_SIZE, RESERVED_SYMBOL_CODES, XFlateDictionary, XFlateDocument, XFlateEstimate,
+    XFlateEvents, XFlateLimits, XFlateOutput, XFlateStats, XmlBBackend, XmlBCodec, XmlBCompress,
+    XmlBDeflateBackend, XmlBDeflateFraming, XmlN, XmlNDynamicSymbolTable, XmlNDynamicTagTable,
+    XmlNEntities, XmlNFormatting, XmlNOptions, XmlNSymbolTable, XmlNTagTable, decode_xmlb_limited,
+    decode_xmln_events, decode_xmln_limited, decode_xmln_lossless, decode_xmls, doctype_header,
+    encode_xmlb, encode_xmln_events, encode_xmln_lossless, encode_xmln_with, encode_xmls,
+    formatting_header, min_symbol_code_size,
+    output::format_xml,
+    read_doctype,
+    scan::{Scan, scan, scan_events},
+    split_doctype_header, split_formatting_header, symbol_capacity, top_frequencies,
+    verify::{EventChecksum, event_checksum, events_checksum, verify, verify_events},
 };
 
 #[derive(Debug)]
@@ -20,6 +37,158 @@ pub enum XFlateError {
     XmlNError(String),
     XmlSError(String),
     XmlBError(String),
+    FormatError(String),
+    LimitExceeded(String),
+    VerificationError(String),
+    /// The decompressed document doesn't match the checksum stored with it
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// A value couldn't be serialized to, or deserialized from, a document
+    SerdeError(String),
+}
+
+impl Display for XFlateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XFlateError::PrePassError(msg) => write!(f, "Pre-pass error: {}", msg),
+            XFlateError::XmlNError(msg) => write!(f, "XMLN error: {}", msg),
+            XFlateError::XmlSError(msg) => write!(f, "XMLS error: {}", msg),
+            XFlateError::XmlBError(msg) => write!(f, "XMLB error: {}", msg),
+            XFlateError::FormatError(msg) => write!(f, "Format error: {}", msg),
+            XFlateError::LimitExceeded(msg) => write!(f, "Limit exceeded: {}", msg),
+            XFlateError::VerificationError(msg) => write!(f, "Verification failed: {}", msg),
+            XFlateError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Checksum mismatch: expected {:08x}, found {:08x}",
+                expected, actual
+            ),
+            XFlateError::SerdeError(msg) => write!(f, "Serde error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for XFlateError {}
+
+/// How XFlate should encode a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XFlateMode {
+    /// Always run the XMLN, XMLS and XMLB stages.
+    XmlN,
+    /// Skip XMLN and only run the backend on the XML bytes.
+    Raw,
+    /// Run both and keep whichever output is smaller.
+    Auto,
+}
+
+/// Encoding actually used for a compressed document.
+/// This is stored as the first byte of the compressed output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum XFlateEncoding {
+    /// Payload was produced by the XMLN, XMLS and XMLB stages
+    #[default]
+    XmlN = 0,
+    /// Payload is the XML compressed by the backend alone
+    Raw = 1,
+    /// Like `XmlN`, with formatting to restore the original bytes
+    Lossless = 2,
+}
+
+/// Set in the encoding flag when a checksum follows the backend ID
+const CHECKSUM_FLAG: u8 = 0x80;
+
+/// Set in the encoding flag when a doctype header follows the table headers
+const DOCTYPE_FLAG: u8 = 0x40;
+
+/// Set in the encoding flag when whitespace was stripped from the document
+const STRIPPED_FLAG: u8 = 0x20;
+
+impl XFlateEncoding {
+    /// Checksum stored for a document with this encoding.
+    /// Raw and lossless documents come back byte for byte, so their
+    /// bytes are hashed. Otherwise it covers elements, attributes and
+    /// text, leaving out whitespace if `flags` tell it was stripped.
+    fn checksum(self, xml: &[u8], flags: u8) -> u32 {
+        match self {
+            XFlateEncoding::XmlN => event_checksum(xml, flags & STRIPPED_FLAG != 0),
+            XFlateEncoding::Raw | XFlateEncoding::Lossless => {
+                let mut crc = Crc::new();
+                crc.update(xml);
+                crc.sum()
+            }
+        }
+    }
+}
+
+impl TryFrom<u8> for XFlateEncoding {
+    type Error = XFlateError;
+
+    fn try_from(flag: u8) -> Result<Self, Self::Error> {
+        match flag {
+            0 => Ok(XFlateEncoding::XmlN),
+            1 => Ok(XFlateEncoding::Raw),
+            2 => Ok(XFlateEncoding::Lossless),
+            _ => Err(XFlateError::FormatError(format!(
+                "Unknown encoding flag: {}",
+                flag
+            ))),
+        }
+    }
+}
+
+/// Parts of compressed data, as written by `XFlate::frame`
+struct Frame<'a> {
+    encoding: XFlateEncoding,
+    /// Flag byte, with the encoding and the optional parts set
+    flags: u8,
+    backend: XmlBBackend,
+    checksum: Option<u32>,
+    payload: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self, XFlateError> {
+        let [flags, backend_id, payload @ ..] = bytes else {
+            return Err(XFlateError::FormatError(
+                "Missing encoding flag or backend".to_string(),
+            ));
+        };
+
+        let encoding =
+            XFlateEncoding::try_from(*flags & !(CHECKSUM_FLAG | DOCTYPE_FLAG | STRIPPED_FLAG))?;
+        let backend = XmlBBackend::try_from(*backend_id)?;
+
+        let (checksum, payload) = match payload {
+            [a, b, c, d, payload @ ..] if flags & CHECKSUM_FLAG != 0 => {
+                (Some(u32::from_le_bytes([*a, *b, *c, *d])), payload)
+            }
+            _ if flags & CHECKSUM_FLAG != 0 => {
+                return Err(XFlateError::FormatError("Missing checksum".to_string()));
+            }
+            _ => (None, payload),
+        };
+
+        Ok(Frame {
+            encoding,
+            flags: *flags,
+            backend,
+            checksum,
+            payload,
+        })
+    }
+}
+
+/// XMLN read from a payload, along with the headers that follow the tables
+struct XmlNPayload {
+    xmln: XmlN,
+    doctype: Option<String>,
+    formatting: Option<XmlNFormatting>,
+    /// Size of the XMLS encoding, excluding headers
+    xmls_bytes: usize,
+    xmlb_time: Duration,
+    xmls_time: Duration,
 }
 
 /// XFlate compression algorithm.
@@ -34,9 +203,6 @@ pub struct XFlate {
     /// into string encodings. Ideally, frequently used
     /// tags/attributes should be assigned to shorter symbols.
     tag_table: XmlNDynamicTagTable,
-    /// Backend compression algorithm.
-    /// This is the last step in the compression process.
-    backend: XmlBDeflateBackend,
     /// Configuration for XFlate compression.
     /// This includes options such as symbol size, backend compression options,
     /// and whether to include headers for symbols and tags.
@@ -45,16 +211,81 @@ pub struct XFlate {
 
 impl XFlate {
     pub fn new(config: XFlateConfig) -> Self {
+        let (sym_table, tag_table) = match &config.dictionary {
+            Some(dictionary) => (
+                dictionary.sym_table().clone(),
+                dictionary.tag_table().clone(),
+            ),
+            None => (
+                XmlNDynamicSymbolTable::new(config.symbol_size),
+                XmlNDynamicTagTable::new(),
+            ),
+        };
+
         XFlate {
-            sym_table: XmlNDynamicSymbolTable::new(config.symbol_size),
-            tag_table: XmlNDynamicTagTable::new(),
-            backend: XmlBDeflateBackend::new(config.xmlb_opt),
+            sym_table,
+            tag_table,
             config,
         }
     }
 
+    /// Create a backend, using the options given in `config`.
+    ///
+    /// Fails for a backend whose feature is disabled, and for a preset
+    /// dictionary with a backend other than deflate, which can't use it.
+    fn make_backend(
+        backend: XmlBBackend,
+        config: &XFlateConfig,
+    ) -> Result<Box<dyn XmlBCodec>, XFlateError> {
+        if backend != XmlBBackend::Deflate && config.dictionary.is_some() {
+            return Err(XFlateError::XmlBError(format!(
+                "Backend {} can't use a preset dictionary",
+                backend
+            )));
+        }
+
+        match backend {
+            XmlBBackend::Deflate => {
+                let deflate = XmlBDeflateBackend::with_options(
+                    config.xmlb_opt,
+                    config.xmlb_framing,
+                    config.xmlb_window_bits,
+                );
+
+                match &config.dictionary {
+                    Some(dictionary) => Ok(Box::new(
+                        deflate.with_dictionary(dictionary.data().to_vec()),
+                    )),
+                    None => Ok(Box::new(deflate)),
+                }
+            }
+            #[cfg(feature = "zstd")]
+            XmlBBackend::Zstd => Ok(Box::new(XmlBZstdBackend::new(config.xmlb_opt))),
+            #[cfg(feature = "brotli")]
+            XmlBBackend::Brotli => Ok(Box::new(XmlBBrotliBackend::new(config.xmlb_opt))),
+            #[cfg(feature = "lzma")]
+            XmlBBackend::Lzma => Ok(Box::new(XmlBLzmaBackend::new(config.xmlb_opt))),
+            #[cfg(not(feature = "zstd"))]
+            XmlBBackend::Zstd => Err(crate::missing_backend("zstd")),
+            #[cfg(not(feature = "brotli"))]
+            XmlBBackend::Brotli => Err(crate::missing_backend("brotli")),
+            #[cfg(not(feature = "lzma"))]
+            XmlBBackend::Lzma => Err(crate::missing_backend("lzma")),
+        }
+    }
+
+    /// Create the configured backend
+    fn backend(&self) -> Result<Box<dyn XmlBCodec>, XFlateError> {
+        XFlate::make_backend(self.config.xmlb_backend, &self.config)
+    }
+
     /// Run XFlate compression on the provided XML.
     ///
+    /// The first byte of the output is an [`XFlateEncoding`] flag and
+    /// the second an [`XmlBBackend`] ID, telling `decompress` how the
+    /// remaining bytes were produced. With `checksum` set, the high bit
+    /// of the flag is set and four checksum bytes follow the ID.
+    ///
     /// # Arguments
     /// * `xml` - The XML data to compress, provided as a `Read` trait object.
     ///
@@ -65,145 +296,877 @@ impl XFlate {
     where
         D: Read,
     {
-        let xmln = encode_xmln(xml, &mut self.sym_table, &mut self.tag_table)?;
-        let xmls = encode_xmls(&xmln)?;
+        self.compress_inner(xml, None)
+    }
+
+    /// Run XFlate compression and report what happened at each stage.
+    ///
+    /// Produces the same output as `compress`. Stages that didn't
+    /// run for the chosen [`XFlateMode`] are reported as zero.
+    ///
+    /// # Returns
+    /// * `Ok((Vec<u8>, XFlateStats))` - Compressed XML and its statistics
+    /// * `Err(XFlateError)` - Error if compression fails
+    pub fn compress_with_stats<D>(
+        &mut self,
+        mut xml: D,
+    ) -> Result<(Vec<u8>, XFlateStats), XFlateError>
+    where
+        D: Read,
+    {
+        let mut buf = Vec::new();
+        xml.read_to_end(&mut buf)
+            .map_err(|e| XFlateError::PrePassError(format!("Failed to read XML: {}", e)))?;
+
+        let mut stats = XFlateStats {
+            xml_bytes: buf.len(),
+            ..Default::default()
+        };
+
+        let out = self.compress_inner(buf.as_slice(), Some(&mut stats))?;
+        stats.output_bytes = out.len();
+
+        Ok((out, stats))
+    }
+
+    /// Compress a document given as events, such as those generated
+    /// by a program, without writing and parsing its text. The events
+    /// are encoded as `compress` would encode the document they make.
+    ///
+    /// Without the text, there is nothing for the plain backend to
+    /// compress or for the formatting to restore. `Raw` mode and the
+    /// lossless encoding are not supported, and `Auto` keeps XMLN.
+    /// CData, comments and processing instructions return an error,
+    /// as XMLN doesn't hold them.
+    pub fn compress_events<I>(&mut self, events: I) -> Result<Vec<u8>, XFlateError>
+    where
+        I: IntoIterator<Item = XmlEvent>,
+    {
+        self.compress_events_inner(events, None)
+    }
+
+    /// Compress a document tree, as `compress_events` would compress
+    /// its events. The doctype is stored as it is, while comments and
+    /// processing instructions are left out, as XMLN doesn't hold them.
+    pub fn compress_document(&mut self, document: &XFlateDocument) -> Result<Vec<u8>, XFlateError> {
+        let events = document.to_events().into_iter().filter(|e| {
+            !matches!(
+                e,
+                XmlEvent::Comment(_) | XmlEvent::ProcessingInstruction { .. }
+            )
+        });
+
+        self.compress_events_inner(events, document.doctype.clone())
+    }
+
+    fn compress_events_inner<I>(
+        &mut self,
+        events: I,
+        doctype: Option<String>,
+    ) -> Result<Vec<u8>, XFlateError>
+    where
+        I: IntoIterator<Item = XmlEvent>,
+    {
+        if self.config.mode == XFlateMode::Raw || self.config.lossless {
+            return Err(XFlateError::PrePassError(
+                "Raw and lossless encodings need the XML text".to_string(),
+            ));
+        }
+
+        // Scanning, checksums and verification go over the events again
+        let events = events.into_iter().collect::<Vec<_>>();
+
+        self.seed_tables(|| scan_events(events.iter().cloned().map(Ok)))?;
+        let options = self.xmln_options();
+
+        let start = Instant::now();
+        let xmln = encode_xmln_events(
+            events.iter().cloned(),
+            &mut self.sym_table,
+            &mut self.tag_table,
+            &options,
+        )?;
+        let (payload, flags) = self.pack_xmln(&xmln, None, doctype, start.elapsed(), None)?;
+
+        let out = self.frame(XFlateEncoding::XmlN, flags, payload, || {
+            events_checksum(&events, flags & STRIPPED_FLAG != 0)
+        });
+
+        if self.config.verify {
+            let decompressed = self.decompress_fresh(&out)?;
+            verify_events(
+                &events,
+                decompressed.as_bytes(),
+                self.config.strip_whitespace,
+            )?;
+        }
+
+        Ok(out)
+    }
+
+    fn compress_inner<D>(
+        &mut self,
+        mut xml: D,
+        mut stats: Option<&mut XFlateStats>,
+    ) -> Result<Vec<u8>, XFlateError>
+    where
+        D: Read,
+    {
+        let mut buf = Vec::new();
+        xml.read_to_end(&mut buf)
+            .map_err(|e| XFlateError::PrePassError(format!("Failed to read XML: {}", e)))?;
+
+        let xmln_encoding = if self.config.lossless {
+            XFlateEncoding::Lossless
+        } else {
+            XFlateEncoding::XmlN
+        };
+
+        // Under Auto, the plain backend output is kept as a fallback
+        let (mut encoding, flags, payload, fallback) = match self.config.mode {
+            XFlateMode::XmlN => {
+                let (xmlb, flags) = self.compress_xmln(buf.as_slice(), stats.as_deref_mut())?;
+                (xmln_encoding, flags, xmlb, None)
+            }
+            XFlateMode::Raw => (
+                XFlateEncoding::Raw,
+                0,
+                self.compress_raw(buf.as_slice(), stats.as_deref_mut())?,
+                None,
+            ),
+            XFlateMode::Auto => {
+                let raw = self.compress_raw(buf.as_slice(), stats.as_deref_mut())?;
+
+                // A document XMLN can't represent is simply left to the backend
+                match self.compress_xmln(buf.as_slice(), stats.as_deref_mut()) {
+                    Ok((xmlb, flags)) if xmlb.len() < raw.len() => {
+                        (xmln_encoding, flags, xmlb, Some(raw))
+                    }
+                    _ => (XFlateEncoding::Raw, 0, raw, None),
+                }
+            }
+        };
+
+        let mut out = self.frame(encoding, flags, payload, || encoding.checksum(&buf, flags));
+
+        if self.config.verify {
+            // The same goes for one XMLN can't reproduce faithfully
+            if let Err(e) = self.verify(&buf, &out) {
+                let raw = fallback.ok_or(e)?;
+                encoding = XFlateEncoding::Raw;
+                out = self.frame(encoding, 0, raw, || encoding.checksum(&buf, 0));
+                self.verify(&buf, &out)?;
+            }
+        }
+
+        if let Some(stats) = stats {
+            stats.encoding = encoding;
+        }
+
+        Ok(out)
+    }
+
+    /// Prefix the payload with the encoding flag, with any extra
+    /// `flags` set, and backend ID, followed by the `checksum`
+    /// of the document if one is wanted
+    fn frame<C>(
+        &self,
+        encoding: XFlateEncoding,
+        flags: u8,
+        payload: Vec<u8>,
+        checksum: C,
+    ) -> Vec<u8>
+    where
+        C: FnOnce() -> u32,
+    {
+        let mut out = Vec::with_capacity(payload.len() + 6);
+
+        if self.config.checksum {
+            out.push(encoding as u8 | flags | CHECKSUM_FLAG);
+            out.push(self.config.xmlb_backend as u8);
+            out.extend(checksum().to_le_bytes());
+        } else {
+            out.push(encoding as u8 | flags);
+            out.push(self.config.xmlb_backend as u8);
+        }
+
+        out.extend(payload);
+
+        out
+    }
+
+    /// Decompress `out` with a fresh instance, and check that it
+    /// holds the same canonical events as the input `xml`.
+    fn verify(&self, xml: &[u8], out: &[u8]) -> Result<(), XFlateError> {
+        let decompressed = self.decompress_fresh(out)?;
+        verify(xml, decompressed.as_bytes(), self.config.strip_whitespace)
+    }
+
+    /// Decompress `out` with a fresh instance, for verification
+    fn decompress_fresh(&self, out: &[u8]) -> Result<String, XFlateError> {
+        XFlate::new(self.config.clone())
+            .decompress(out)
+            .map_err(|e| {
+                XFlateError::VerificationError(format!("Output failed to decompress: {:?}", e))
+            })
+    }
+
+    /// Compress the XML through the XMLN, XMLS and XMLB stages.
+    /// Returns the payload and the flags for the optional headers in it.
+    fn compress_xmln<D>(
+        &mut self,
+        mut xml: D,
+        stats: Option<&mut XFlateStats>,
+    ) -> Result<(Vec<u8>, u8), XFlateError>
+    where
+        D: Read,
+    {
+        let mut buf = Vec::new();
+        xml.read_to_end(&mut buf)
+            .map_err(|e| XFlateError::PrePassError(format!("Failed to read XML: {}", e)))?;
+
+        self.seed_tables(|| scan(buf.as_slice()))?;
+        let options = self.xmln_options();
+
+        // The formatting of a lossless encoding holds the doctype
+        let start = Instant::now();
+        let (xmln, formatting, doctype) = if self.config.lossless {
+            let (xmln, formatting) =
+                encode_xmln_lossless(&buf, &mut self.sym_table, &mut self.tag_table, &options)?;
+            (xmln, Some(formatting), None)
+        } else {
+            let xmln = encode_xmln_with(
+                buf.as_slice(),
+                &mut self.sym_table,
+                &mut self.tag_table,
+                &options,
+            )?;
+            (xmln, None, read_doctype(&buf)?)
+        };
+        let xmln_time = start.elapsed();
+
+        self.pack_xmln(&xmln, formatting, doctype, xmln_time, stats)
+    }
+
+    /// Options for the XMLN stage, as given in the config
+    fn xmln_options(&self) -> XmlNOptions {
+        XmlNOptions {
+            entities: self.config.entities,
+            indentation: self.config.indentation,
+            strip_whitespace: self.config.strip_whitespace,
+        }
+    }
+
+    /// Run the XMLS and XMLB stages on the XMLN, with the headers.
+    /// Returns the payload and the flags for the optional headers in it.
+    fn pack_xmln(
+        &mut self,
+        xmln: &str,
+        formatting: Option<XmlNFormatting>,
+        doctype: Option<String>,
+        xmln_time: Duration,
+        stats: Option<&mut XFlateStats>,
+    ) -> Result<(Vec<u8>, u8), XFlateError> {
+        let start = Instant::now();
+        let xmls = encode_xmls(xmln)?;
+        let xmls_time = start.elapsed();
+        let xmls_bytes = xmls.len();
+
+        // Prepend formatting header
+        let (xmls, formatting_bytes) = match formatting {
+            Some(formatting) => {
+                let mut header = formatting_header(&formatting);
+                let header_len = header.len();
+                header.extend(xmls);
+                (header, header_len)
+            }
+            None => (xmls, 0),
+        };
+
+        // Prepend doctype header
+        let (xmls, doctype_bytes) = match &doctype {
+            Some(doctype) => {
+                let mut header = doctype_header(doctype);
+                let header_len = header.len();
+                header.extend(xmls);
+                (header, header_len)
+            }
+            None => (xmls, 0),
+        };
 
         // Prepend symbol header
-        let xmls = if self.config.add_symbol_header {
+        let (xmls, symbol_header_bytes) = if self.config.add_symbol_header {
             let mut header = self.sym_table.to_header();
+            let header_len = header.len();
             header.extend(xmls);
-            header
+            (header, header_len)
         } else {
-            xmls
+            (xmls, 0)
         };
 
         // Prepend tag header
-        let xmls = if self.config.add_tag_header {
+        let (xmls, tag_header_bytes) = if self.config.add_tag_header {
             let mut header = self.tag_table.to_header();
+            let header_len = header.len();
             header.extend(xmls);
-            header
+            (header, header_len)
         } else {
-            xmls
+            (xmls, 0)
         };
 
-        let xmlb = encode_xmlb(&xmls, &self.backend)?;
+        let start = Instant::now();
+        let xmlb = encode_xmlb(&xmls, self.backend()?.as_ref())?;
+        let xmlb_time = start.elapsed();
+
+        if let Some(stats) = stats {
+            stats.xmln_bytes = xmln.len();
+            stats.xmls_bytes = xmls_bytes;
+            stats.tag_header_bytes = tag_header_bytes;
+            stats.symbol_header_bytes = symbol_header_bytes;
+            stats.formatting_bytes = formatting_bytes;
+            stats.doctype_bytes = doctype_bytes;
+            stats.xmlb_bytes = xmlb.len();
+            stats.tag_count = self.tag_table.tag_count();
+            stats.symbol_count = self.sym_table.symbol_count();
+            stats.code_size = self.sym_table.code_size();
+            stats.xmln_time = xmln_time;
+            stats.xmls_time = xmls_time;
+            stats.xmlb_time = xmlb_time;
+            (stats.top_tags, stats.top_symbols) =
+                top_frequencies(xmln, &self.sym_table, &self.tag_table)?;
+        }
+
+        let mut flags = 0;
+        if doctype.is_some() {
+            flags |= DOCTYPE_FLAG;
+        }
+        if self.config.strip_whitespace && !self.config.lossless {
+            flags |= STRIPPED_FLAG;
+        }
 
-        Ok(xmlb)
+        Ok((xmlb, flags))
     }
 
-    pub fn decompress<D>(&mut self, binary: D) -> Result<String, XFlateError>
+    /// Assign codes ahead of encoding, for table orders that don't
+    /// match the order tags and symbols are first seen in. The document
+    /// is only scanned if one of them does.
+    fn seed_tables<F>(&mut self, scan: F) -> Result<(), XFlateError>
+    where
+        F: FnOnce() -> Result<Scan, XFlateError>,
+    {
+        let first_seen = self.config.tag_order == XFlateTableOrder::FirstSeen
+            && self.config.symbol_order == XFlateTableOrder::FirstSeen;
+        if first_seen && self.config.dictionary.is_none() {
+            return Ok(());
+        }
+
+        let scan = scan()?;
+        if self.config.dictionary.is_some() {
+            self.make_room_for_symbols(&scan)?;
+        }
+        if first_seen {
+            return Ok(());
+        }
+
+        for tag in self.config.tag_order.apply(scan.tags) {
+            self.tag_table
+                .encode(&tag)
+                .ok_or_else(|| XFlateError::XmlNError(format!("Failed to encode tag: {}", tag)))?;
+        }
+
+        for symbol in self.config.symbol_order.apply(scan.symbols) {
+            self.sym_table.encode(symbol).ok_or_else(|| {
+                XFlateError::XmlNError(format!("Failed to encode symbol: {}", symbol))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Widen the symbol codes when the symbols of the document don't
+    /// all fit in the table, as when a dictionary trained on other
+    /// documents fills it. Known symbols keep their order, and the
+    /// table header written with the output holds the new size.
+    fn make_room_for_symbols(&mut self, scan: &Scan) -> Result<(), XFlateError> {
+        let known = self.sym_table.iter_symbols().collect::<HashSet<_>>();
+        let unseen = scan
+            .symbols
+            .iter()
+            .filter(|(symbol, _)| !known.contains(symbol))
+            .count();
+
+        let needed = known.len() + unseen;
+        if needed <= symbol_capacity(self.sym_table.code_size()) {
+            return Ok(());
+        }
+
+        let code_size = min_symbol_code_size(needed).ok_or_else(|| {
+            XFlateError::PrePassError(format!(
+                "No symbol size can encode {} unique symbols",
+                needed
+            ))
+        })?;
+
+        let mut sym_table = XmlNDynamicSymbolTable::new(code_size);
+        for symbol in self.sym_table.iter_symbols() {
+            sym_table.encode(symbol);
+        }
+        self.sym_table = sym_table;
+
+        Ok(())
+    }
+
+    /// Compress the XML bytes directly with the backend.
+    fn compress_raw<D>(
+        &self,
+        mut xml: D,
+        stats: Option<&mut XFlateStats>,
+    ) -> Result<Vec<u8>, XFlateError>
     where
         D: Read,
     {
-        let xmlb: Vec<u8> = binary
-            .bytes()
-            .collect::<Result<_, _>>()
-            .map_err(|e| XFlateError::XmlBError(format!("Failed to read bytes: {}", e)))?;
+        let mut buf = Vec::new();
+        xml.read_to_end(&mut buf)
+            .map_err(|e| XFlateError::PrePassError(format!("Failed to read XML: {}", e)))?;
 
-        // Ugly hack, should probably work.
-        // The idea here is that our header information
-        // must be first and a valid utf8 chunk.
-        let xmls_raw_bytes = decode_xmlb(xmlb.as_slice(), &self.backend)?;
-        let mut xmls_chunks = xmls_raw_bytes.utf8_chunks();
+        let start = Instant::now();
+        let raw = encode_xmlb(&buf, self.backend()?.as_ref())?;
 
-        let header_chunk = xmls_chunks.next().ok_or(XFlateError::XmlSError(
-            "Unable to locate header".to_string(),
-        ))?;
+        if let Some(stats) = stats {
+            stats.raw_bytes = raw.len();
+            stats.raw_time = start.elapsed();
+        }
 
-        let mut header_chars = header_chunk.valid().chars().multipeek();
+        Ok(raw)
+    }
 
-        // Look for tag header
-        self.tag_table = XFlate::parse_tag_header(&mut header_chars)?;
+    /// Search for the configuration giving the smallest output.
+    ///
+    /// Every combination of candidate symbol sizes, table orders and
+    /// backend options is tried, along with the plain backend. Each of
+    /// these only affects data stored in the output, so `decompress`
+    /// needs no extra input. Afterwards, `config` returns the winner.
+    /// When the document can't be scanned for its symbols, as with
+    /// markup XMLN doesn't hold, only the plain backend is tried.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The smallest compressed output found
+    /// * `Err(XFlateError)` - Error if no candidate could compress the XML
+    pub fn compress_best<D>(&mut self, mut xml: D) -> Result<Vec<u8>, XFlateError>
+    where
+        D: Read,
+    {
+        let mut buf = Vec::new();
+        xml.read_to_end(&mut buf)
+            .map_err(|e| XFlateError::PrePassError(format!("Failed to read XML: {}", e)))?;
 
-        // Look for symbol header
-        self.sym_table = XFlate::parse_symbol_header(&mut header_chars)?;
+        let mut best: Option<(Vec<u8>, XFlate)> = None;
+        let mut last_err = None;
 
-        // Again real ugly, but collect everything that
-        // wasn't parsed from the header as bytes again
-        let mut xmls: Vec<u8> = header_chars.collect::<String>().into();
-        xmls.extend(header_chunk.invalid());
-        for chunk in xmls_chunks {
-            xmls.extend(chunk.valid().as_bytes());
-            xmls.extend(chunk.invalid());
+        for config in self.config.candidates(buf.as_slice()) {
+            let mut xflate = XFlate::new(config);
+            match xflate.compress(buf.as_slice()) {
+                Ok(out) if best.as_ref().is_none_or(|(b, _)| out.len() < b.len()) => {
+                    best = Some((out, xflate));
+                }
+                Ok(_) => continue,
+                Err(e) => last_err = Some(e),
+            }
         }
 
-        let xmln = decode_xmls(xmls.as_slice())?;
+        let (out, xflate) = best.ok_or_else(|| {
+            last_err.unwrap_or(XFlateError::PrePassError(
+                "No configuration to try".to_string(),
+            ))
+        })?;
+        *self = xflate;
+
+        Ok(out)
+    }
+
+    /// Returns the configuration in use
+    pub fn config(&self) -> &XFlateConfig {
+        &self.config
+    }
+
+    pub fn decompress<D>(&mut self, binary: D) -> Result<String, XFlateError>
+    where
+        D: Read,
+    {
+        self.decompress_inner(binary, None)
+    }
 
-        decode_xmln(xmln.as_str(), &mut self.sym_table, &mut self.tag_table)
+    /// Decompress the data and write the document with the given
+    /// layout. Any checksum is checked against the stored document.
+    pub fn decompress_with<D>(
+        &mut self,
+        binary: D,
+        output: &XFlateOutput,
+    ) -> Result<String, XFlateError>
+    where
+        D: Read,
+    {
+        let xml = self.decompress_inner(binary, None)?;
+        format_xml(&xml, output)
     }
 
-    /// Hacky parser for tag table header.
+    /// Decompress the data, passing the XML to `sink` a block at a
+    /// time as it is decoded, rather than returning it whole.
     ///
-    /// TODO: move this into the tag table struct.
-    fn parse_tag_header(
-        chars: &mut MultiPeek<Chars<'_>>,
-    ) -> Result<XmlNDynamicTagTable, XFlateError> {
-        chars.next(); // 'E'
-        chars.next(); // ' '
-
-        let tag_count = consume_until_space(chars)
-            .parse::<usize>()
-            .map_err(|e| XFlateError::XmlSError(format!("Unable to parse tag count {}", e)))?;
-
-        chars.next(); // ' '
-
-        let mut tag_table = XmlNDynamicTagTable::new();
-        for _ in 0..tag_count {
-            let tag = consume_until_space(chars);
-            chars.next(); // ' '
-            if tag_table.encode(tag.as_str()).is_none() {
-                return Err(XFlateError::XmlSError(format!(
-                    "Unable to add tag {} to tag table",
-                    tag
-                )));
+    /// A stored checksum is checked by reading the document's events
+    /// before anything is passed on. Raw and lossless documents are
+    /// only restored once they are whole, so they come out in one block.
+    #[cfg(feature = "async")]
+    pub(crate) fn decompress_to<D, F>(
+        &mut self,
+        mut binary: D,
+        mut sink: F,
+    ) -> Result<(), XFlateError>
+    where
+        D: Read,
+        F: FnMut(&str) -> Result<(), XFlateError>,
+    {
+        let mut xmlb: Vec<u8> = Vec::new();
+        binary
+            .read_to_end(&mut xmlb)
+            .map_err(|e| XFlateError::XmlBError(format!("Failed to read bytes: {}", e)))?;
+
+        let frame = Frame::parse(&xmlb)?;
+        if frame.encoding != XFlateEncoding::XmlN {
+            let xml = self.decompress_inner(xmlb.as_slice(), None)?;
+            return sink(&xml);
+        }
+
+        if frame.checksum.is_some() {
+            for event in self.decompress_events(xmlb.as_slice())? {
+                event?;
             }
         }
 
-        Ok(tag_table)
+        let backend = XFlate::make_backend(frame.backend, &self.config)?;
+        let payload = self.unpack_xmln(
+            frame.payload,
+            backend.as_ref(),
+            frame.flags & DOCTYPE_FLAG != 0,
+            false,
+        )?;
+
+        if let Some(doctype) = &payload.doctype {
+            sink(doctype)?;
+        }
+
+        decode_xmln_streamed(
+            payload.xmln.as_str(),
+            &mut self.sym_table,
+            &mut self.tag_table,
+            &self.config.limits,
+            sink,
+        )
     }
 
-    /// Hacky parser for symbol table header.
+    /// Decompress the data and report what it holds at each stage.
     ///
-    /// TODO: move this into the symbol table struct.
-    fn parse_symbol_header(
-        chars: &mut MultiPeek<Chars<'_>>,
-    ) -> Result<XmlNDynamicSymbolTable, XFlateError> {
-        chars.next(); // 'C'
-        chars.next(); // ' '
+    /// Byte counts are those of the stored stages, and durations are
+    /// the time spent decoding each of them. Afterwards, `tag_table`
+    /// and `sym_table` return the tables read from the headers.
+    pub fn inspect<D>(&mut self, binary: D) -> Result<XFlateStats, XFlateError>
+    where
+        D: Read,
+    {
+        let mut stats = XFlateStats::default();
+        let xml = self.decompress_inner(binary, Some(&mut stats))?;
+        stats.xml_bytes = xml.len();
+
+        Ok(stats)
+    }
 
-        let code_size = consume_until_space(chars)
-            .parse::<u8>()
-            .map_err(|e| XFlateError::XmlSError(format!("Unable to prase the code size {}", e)))?;
+    fn decompress_inner<D>(
+        &mut self,
+        mut binary: D,
+        mut stats: Option<&mut XFlateStats>,
+    ) -> Result<String, XFlateError>
+    where
+        D: Read,
+    {
+        let mut xmlb: Vec<u8> = Vec::new();
+        binary
+            .read_to_end(&mut xmlb)
+            .map_err(|e| XFlateError::XmlBError(format!("Failed to read bytes: {}", e)))?;
 
-        chars.next(); // ' '
+        let frame = Frame::parse(&xmlb)?;
+        let encoding = frame.encoding;
+        let doctype = frame.flags & DOCTYPE_FLAG != 0;
+        let backend = XFlate::make_backend(frame.backend, &self.config)?;
+        let xmlb = frame.payload;
 
-        let symbol_count = consume_until_space(chars).parse::<usize>().map_err(|e| {
-            XFlateError::XmlSError(format!("Unable to parse the symbol count {}", e))
-        })?;
+        if let Some(stats) = stats.as_deref_mut() {
+            stats.encoding = encoding;
+            stats.output_bytes = xmlb.len() + 2 + frame.checksum.map_or(0, |_| 4);
+        }
 
-        chars.next(); // ' '
+        let xml = match encoding {
+            XFlateEncoding::XmlN => {
+                self.decompress_xmln(xmlb, backend.as_ref(), doctype, false, stats)?
+            }
+            XFlateEncoding::Lossless => {
+                self.decompress_xmln(xmlb, backend.as_ref(), doctype, true, stats)?
+            }
+            XFlateEncoding::Raw if doctype => {
+                return Err(XFlateError::FormatError(
+                    "Raw encoding can't have a doctype header".to_string(),
+                ));
+            }
+            XFlateEncoding::Raw => {
+                let start = Instant::now();
+                let xml = decode_xmlb_limited(
+                    xmlb,
+                    backend.as_ref(),
+                    self.config.limits.max_output_bytes,
+                )?;
 
-        let mut sym_table = XmlNDynamicSymbolTable::new(code_size);
+                if let Some(stats) = stats {
+                    stats.raw_bytes = xmlb.len();
+                    stats.raw_time = start.elapsed();
+                }
+
+                String::from_utf8(xml).map_err(|e| {
+                    XFlateError::XmlBError(format!("Decompressed XML is not valid UTF-8: {}", e))
+                })?
+            }
+        };
 
-        for _ in 0..symbol_count {
-            let s = chars.next().ok_or(XFlateError::XmlSError(
-                "Symbol header is missing symbols".to_string(),
-            ))?;
-            if sym_table.encode(s).is_none() {
-                return Err(XFlateError::XmlSError(format!(
-                    "Unable to add symbol {} to the symbol table",
-                    s
-                )));
+        if let Some(expected) = frame.checksum {
+            let actual = encoding.checksum(xml.as_bytes(), frame.flags);
+            if actual != expected {
+                return Err(XFlateError::ChecksumMismatch { expected, actual });
             }
         }
 
-        chars.next(); // ' '
+        Ok(xml)
+    }
+
+    /// Decompress the data into the events a parser would read from
+    /// the document, decoded as they are read. Callers can write them
+    /// elsewhere, with `XmlEvent::as_writer_event`, or stop early.
+    ///
+    /// The backend and headers are decoded up front. A stored checksum
+    /// is checked once the last event is read, and a mismatch is then
+    /// returned in place of `EndDocument`. Raw and lossless documents
+    /// are checksummed by their bytes, so they are decompressed first
+    /// and their text is parsed.
+    pub fn decompress_events<D>(&mut self, mut binary: D) -> Result<XFlateEvents, XFlateError>
+    where
+        D: Read,
+    {
+        let mut xmlb: Vec<u8> = Vec::new();
+        binary
+            .read_to_end(&mut xmlb)
+            .map_err(|e| XFlateError::XmlBError(format!("Failed to read bytes: {}", e)))?;
+
+        let frame = Frame::parse(&xmlb)?;
+        if frame.encoding != XFlateEncoding::XmlN {
+            let xml = self.decompress_inner(xmlb.as_slice(), None)?;
+            return XFlateEvents::parse(xml);
+        }
+
+        let backend = XFlate::make_backend(frame.backend, &self.config)?;
+        let payload = self.unpack_xmln(
+            frame.payload,
+            backend.as_ref(),
+            frame.flags & DOCTYPE_FLAG != 0,
+            false,
+        )?;
+
+        let events = decode_xmln_events(
+            payload.xmln,
+            self.sym_table.clone(),
+            self.tag_table.clone(),
+            &self.config.limits,
+        );
+        let checksum = frame.checksum.map(|expected| {
+            (
+                EventChecksum::new(frame.flags & STRIPPED_FLAG != 0),
+                expected,
+            )
+        });
+
+        Ok(XFlateEvents::decoded(events, payload.doctype, checksum))
+    }
+
+    /// Decompress the data into a document tree, built from the
+    /// events returned by `decompress_events`.
+    pub fn decompress_document<D>(&mut self, binary: D) -> Result<XFlateDocument, XFlateError>
+    where
+        D: Read,
+    {
+        let events = self.decompress_events(binary)?;
+        let doctype = events.doctype().map(str::to_string);
+
+        XFlateDocument::from_events(events, doctype)
+    }
+
+    /// Decompress a payload produced by `compress_xmln`. The tables
+    /// may be followed by a doctype header, and a formatting header
+    /// for a lossless payload.
+    fn decompress_xmln(
+        &mut self,
+        xmlb: &[u8],
+        backend: &dyn XmlBCodec,
+        doctype: bool,
+        lossless: bool,
+        stats: Option<&mut XFlateStats>,
+    ) -> Result<String, XFlateError> {
+        let limits = self.config.limits;
+        let payload = self.unpack_xmln(xmlb, backend, doctype, lossless)?;
+
+        let start = Instant::now();
+        let xml = match &payload.formatting {
+            Some(formatting) => decode_xmln_lossless(
+                payload.xmln.as_str(),
+                formatting,
+                &mut self.sym_table,
+                &mut self.tag_table,
+                &limits,
+            )?,
+            None => decode_xmln_limited(
+                payload.xmln.as_str(),
+                &mut self.sym_table,
+                &mut self.tag_table,
+                &limits,
+            )?,
+        };
+        let xml = match &payload.doctype {
+            Some(doctype) => doctype.to_string() + &xml,
+            None => xml,
+        };
+        let xmln_time = start.elapsed();
+
+        if let Some(stats) = stats {
+            stats.xmln_bytes = payload.xmln.len();
+            stats.xmls_bytes = payload.xmls_bytes;
+            stats.tag_header_bytes = self.tag_table.to_header().len();
+            stats.symbol_header_bytes = self.sym_table.to_header().len();
+            stats.formatting_bytes = payload
+                .formatting
+                .as_ref()
+                .map_or(0, |f| formatting_header(f).len());
+            stats.doctype_bytes = payload
+                .doctype
+                .as_ref()
+                .map_or(0, |d| doctype_header(d).len());
+            stats.xmlb_bytes = xmlb.len();
+            stats.tag_count = self.tag_table.tag_count();
+            stats.symbol_count = self.sym_table.symbol_count();
+            stats.code_size = self.sym_table.code_size();
+            stats.xmln_time = xmln_time;
+            stats.xmls_time = payload.xmls_time;
+            stats.xmlb_time = payload.xmlb_time;
+            (stats.top_tags, stats.top_symbols) =
+                top_frequencies(&payload.xmln, &self.sym_table, &self.tag_table)?;
+        }
+
+        Ok(xml)
+    }
+
+    /// Run the backend and XMLS stages on a payload produced by
+    /// `compress_xmln`, reading the tables into `self` on the way
+    fn unpack_xmln(
+        &mut self,
+        xmlb: &[u8],
+        backend: &dyn XmlBCodec,
+        doctype: bool,
+        lossless: bool,
+    ) -> Result<XmlNPayload, XFlateError> {
+        let start = Instant::now();
+        let limits = self.config.limits;
+
+        // How large the payload may be depends on the size of the symbol
+        // codes, so the headers are read first. They must fit within
+        // `max_header_bytes`, and smaller payloads come out whole.
+        let head = backend.decompress_prefix(xmlb, limits.max_header_bytes)?;
+        let xmls_raw_bytes = if head.len() < limits.max_header_bytes {
+            head
+        } else {
+            // Headers within the limit always parse from the prefix
+            let (_, sym_table, _) = XFlate::split_headers(&head, &limits).map_err(|e| {
+                XFlateError::LimitExceeded(format!(
+                    "Headers don't fit within the limit of {}: {}",
+                    limits.max_header_bytes, e
+                ))
+            })?;
+            let limit = limits.max_payload_bytes(sym_table.code_size(), lossless);
+            decode_xmlb_limited(xmlb, backend, limit)?
+        };
+        let xmlb_time = start.elapsed();
+
+        let (tag_table, sym_table, xmls) = XFlate::split_headers(&xmls_raw_bytes, &limits)?;
+        self.tag_table = tag_table;
+        self.sym_table = sym_table;
+
+        let (doctype, xmls) = if doctype {
+            let (doctype, xmls) = split_doctype_header(xmls)?;
+            limits.check_header(doctype.len())?;
+            (Some(doctype.to_string()), xmls)
+        } else {
+            (None, xmls)
+        };
+
+        let (formatting, xmls) = if lossless {
+            let (formatting, xmls) = split_formatting_header(xmls)?;
+            limits.check_output(formatting.len())?;
+            (Some(formatting.to_vec()), xmls)
+        } else {
+            (None, xmls)
+        };
+
+        let start = Instant::now();
+        let xmln = decode_xmls(xmls)?;
+        let xmls_time = start.elapsed();
+
+        Ok(XmlNPayload {
+            xmln,
+            doctype,
+            formatting,
+            xmls_bytes: xmls.len(),
+            xmlb_time,
+            xmls_time,
+        })
+    }
+
+    /// Returns the tag table, as built by the last
+    /// compression or read by the last decompression
+    pub fn tag_table(&self) -> &XmlNDynamicTagTable {
+        &self.tag_table
+    }
 
-        Ok(sym_table)
+    /// Returns the symbol table, as built by the last
+    /// compression or read by the last decompression
+    pub fn sym_table(&self) -> &XmlNDynamicSymbolTable {
+        &self.sym_table
+    }
+
+    /// Parse the tag and symbol headers at the start of `bytes`.
+    /// Returns the tables and the bytes following the headers.
+    pub(crate) fn split_headers<'a>(
+        bytes: &'a [u8],
+        limits: &XFlateLimits,
+    ) -> Result<(XmlNDynamicTagTable, XmlNDynamicSymbolTable, &'a [u8]), XFlateError> {
+        let (tag_table, tag_header_len) = XmlNDynamicTagTable::from_header(bytes)?;
+        limits.check_table_entries(tag_table.tag_count())?;
+
+        let (sym_table, sym_header_len) =
+            XmlNDynamicSymbolTable::from_header(&bytes[tag_header_len..])?;
+        limits.check_table_entries(sym_table.symbol_count())?;
+
+        let header_len = tag_header_len + sym_header_len;
+        limits.check_header(header_len)?;
+
+        Ok((tag_table, sym_table, &bytes[header_len..]))
     }
 }
 
 /// Configuration struct for XFlate compression.
+#[derive(Debug, Clone)]
 pub struct XFlateConfig {
     /// Size of symbols used when encoding XMLN.
     /// A symbol size of 1 can handle 9 unique symbols,
@@ -216,6 +1179,18 @@ pub struct XFlateConfig {
     /// optimize for speed or for compression ratio.
     pub xmlb_opt: XmlBCompress,
 
+    /// Backend compression algorithm.
+    /// The options below only apply to the deflate backend.
+    pub xmlb_backend: XmlBBackend,
+
+    /// Container around the backend's deflate stream.
+    /// zlib and gzip framing add a checksum of the XMLS data.
+    pub xmlb_framing: XmlBDeflateFraming,
+
+    /// Deflate window size as a base two logarithm, from 9 to 15.
+    /// `None` uses the largest window. Requires the `fast` feature.
+    pub xmlb_window_bits: Option<u8>,
+
     /// Tells XFlate to include a header with symbol information.
     /// This header can be used to decode the compressed XMLN data.
     ///
@@ -227,21 +1202,221 @@ pub struct XFlateConfig {
     ///
     /// Note that false is not supported, yet.
     pub add_tag_header: bool,
+
+    /// Whether to use the XMLN pipeline, the plain backend,
+    /// or pick the smaller of the two per document.
+    pub mode: XFlateMode,
+
+    /// Order in which tags and attribute names are given codes.
+    pub tag_order: XFlateTableOrder,
+
+    /// Order in which characters are given codes.
+    pub symbol_order: XFlateTableOrder,
+
+    /// Dictionary shared by compression and decompression.
+    /// Its symbol table takes precedence over `symbol_size`, and
+    /// gets longer codes when a document brings more new symbols
+    /// than it has room for.
+    /// Its preset data is only used by the deflate backend,
+    /// which requires the `fast` feature for it. Other backends
+    /// return an error when a dictionary is given.
+    pub dictionary: Option<XFlateDictionary>,
+
+    /// Limits on what decompression will allocate,
+    /// for data that comes from untrusted sources.
+    pub limits: XFlateLimits,
+
+    /// Tells XFlate to decompress its own output after compressing,
+    /// and fail unless the elements, attributes and text match
+    /// those of the input. This roughly doubles the time taken.
+    pub verify: bool,
+
+    /// Tells XFlate to store a CRC-32 of the document, which
+    /// `decompress` checks to detect corruption. For the XMLN
+    /// encoding, it covers the elements, attributes and text.
+    pub checksum: bool,
+
+    /// Tells XFlate to record what XMLN leaves out, such as quotes,
+    /// entity references and comments, so that `decompress` returns
+    /// the input byte for byte. The document must be UTF-8.
+    pub lossless: bool,
+
+    /// Whether references to entities declared in the document type
+    /// are kept as references or replaced with their values.
+    pub entities: XmlNEntities,
+
+    /// Tells XFlate to encode whitespace following the indentation of
+    /// the document with a single token. This doesn't change the output.
+    pub indentation: bool,
+
+    /// Tells XFlate to leave out text made only of whitespace, except
+    /// in elements with `xml:space="preserve"`. This changes the document,
+    /// unless `lossless` is set.
+    pub strip_whitespace: bool,
+}
+
+/// Order in which a table assigns codes to its entries.
+/// The order is recorded in the table headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XFlateTableOrder {
+    /// Codes follow the order entries appear in the document
+    FirstSeen,
+    /// The most frequent entries get the lowest codes.
+    /// Requires an extra scan over the document.
+    Frequency,
+}
+
+impl XFlateTableOrder {
+    /// Order scanned entries, given in first-seen order with their counts
+    fn apply<T>(self, mut entries: Vec<(T, usize)>) -> impl Iterator<Item = T> {
+        if self == XFlateTableOrder::Frequency {
+            // Stable, so ties keep their first-seen order
+            entries.sort_by(|(_, a), (_, b)| b.cmp(a));
+        }
+
+        entries.into_iter().map(|(entry, _)| entry)
+    }
 }
 
 impl XFlateConfig {
+    /// Creates a default configuration with a symbol size
+    /// inferred from the document. See `infer_symbol_size`.
     pub fn from_xml<D>(xml: D) -> Result<Self, XFlateError>
     where
         D: Read,
     {
-        let unique_symbols = scan(xml)?;
-        let symbol_size = (unique_symbols as f64).log(10.0).ceil() as u8;
+        let inferred = XFlateConfig::infer_symbol_size(xml)?;
 
         Ok(XFlateConfig {
-            symbol_size,
+            symbol_size: inferred.symbol_size,
             ..Default::default()
         })
     }
+
+    /// Scans the document and picks the smallest symbol size
+    /// able to encode every unique character in it.
+    ///
+    /// # Returns
+    /// * `Ok(XFlateSymbolSize)` - The chosen size and how it was reached
+    /// * `Err(XFlateError)` - If the document can't be scanned, or has
+    ///   more unique characters than any symbol size can hold
+    pub fn infer_symbol_size<D>(xml: D) -> Result<XFlateSymbolSize, XFlateError>
+    where
+        D: Read,
+    {
+        XFlateSymbolSize::from_scan(&scan(xml)?)
+    }
+
+    /// Scans the document and projects how well it compresses,
+    /// with and without XMLN, without running the encoders.
+    /// The symbol size is inferred as in `infer_symbol_size`.
+    ///
+    /// # Returns
+    /// * `Ok(XFlateEstimate)` - Predicted sizes for the document
+    /// * `Err(XFlateError)` - If the document can't be scanned
+    pub fn estimate<D>(xml: D) -> Result<XFlateEstimate, XFlateError>
+    where
+        D: Read,
+    {
+        let scan = scan(xml)?;
+        let symbol_size = XFlateSymbolSize::from_scan(&scan)?;
+
+        Ok(XFlateEstimate::from_scan(&scan, symbol_size))
+    }
+}
+
+impl XFlateConfig {
+    /// Configurations tried by `XFlate::compress_best`,
+    /// keeping the options that aren't searched over. XMLN candidates
+    /// are left out when the document fails to scan.
+    fn candidates(&self, xml: &[u8]) -> Vec<XFlateConfig> {
+        let sizes = match XFlateConfig::infer_symbol_size(xml) {
+            Ok(min) => vec![
+                min.symbol_size,
+                min.symbol_size.saturating_add(1).min(MAX_SYMBOL_CODE_SIZE),
+            ],
+            Err(_) => Vec::new(),
+        };
+        let orders = [XFlateTableOrder::FirstSeen, XFlateTableOrder::Frequency];
+        let levels = [XmlBCompress::Fast, XmlBCompress::Best];
+
+        // A dictionary rules out every backend but deflate
+        let backends = XmlBBackend::ALL
+            .iter()
+            .filter(|&&backend| self.dictionary.is_none() || backend == XmlBBackend::Deflate);
+
+        let mut candidates = Vec::new();
+        for (&xmlb_backend, xmlb_opt) in backends.cartesian_product(levels) {
+            candidates.push(XFlateConfig {
+                xmlb_opt,
+                xmlb_backend,
+                mode: XFlateMode::Raw,
+                ..self.clone()
+            });
+
+            for &symbol_size in sizes.iter().dedup() {
+                for (tag_order, symbol_order) in orders.into_iter().cartesian_product(orders) {
+                    candidates.push(XFlateConfig {
+                        symbol_size,
+                        xmlb_opt,
+                        tag_order,
+                        symbol_order,
+                        xmlb_backend,
+                        mode: XFlateMode::XmlN,
+                        ..self.clone()
+                    });
+                }
+            }
+        }
+
+        candidates
+    }
+}
+
+impl XFlateSymbolSize {
+    fn from_scan(scan: &Scan) -> Result<Self, XFlateError> {
+        let unique_symbols = scan.unique_symbols();
+        let symbol_size = min_symbol_code_size(unique_symbols).ok_or_else(|| {
+            XFlateError::PrePassError(format!(
+                "No symbol size can encode {} unique symbols",
+                unique_symbols
+            ))
+        })?;
+
+        Ok(XFlateSymbolSize {
+            unique_symbols,
+            reserved_codes: RESERVED_SYMBOL_CODES,
+            capacity: symbol_capacity(symbol_size),
+            symbol_size,
+        })
+    }
+}
+
+/// Outcome of inferring a symbol size from a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XFlateSymbolSize {
+    /// Number of unique characters found in the document
+    pub unique_symbols: usize,
+    /// Number of codes that can't be assigned to characters
+    pub reserved_codes: usize,
+    /// Number of characters that fit at the chosen size
+    pub capacity: usize,
+    /// The chosen symbol size
+    pub symbol_size: u8,
+}
+
+impl Display for XFlateSymbolSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} unique symbols need a symbol size of {} ({} codes, {} reserved, {} usable)",
+            self.unique_symbols,
+            self.symbol_size,
+            self.capacity + self.reserved_codes,
+            self.reserved_codes,
+            self.capacity
+        )
+    }
 }
 
 impl Default for XFlateConfig {
@@ -249,68 +1424,22 @@ impl Default for XFlateConfig {
         XFlateConfig {
             symbol_size: 2,
             xmlb_opt: XmlBCompress::Best,
+            xmlb_backend: XmlBBackend::Deflate,
+            xmlb_framing: XmlBDeflateFraming::Raw,
+            xmlb_window_bits: None,
             add_symbol_header: true,
             add_tag_header: true,
+            mode: XFlateMode::XmlN,
+            tag_order: XFlateTableOrder::FirstSeen,
+            symbol_order: XFlateTableOrder::FirstSeen,
+            dictionary: None,
+            limits: XFlateLimits::default(),
+            verify: false,
+            checksum: false,
+            lossless: false,
+            entities: XmlNEntities::Expand,
+            indentation: true,
+            strip_whitespace: false,
         }
     }
 }
-
-/// Run a scan over the XML data.
-/// This counts the number of unique symbols in the XML data.
-///
-/// # Warning
-/// There is no guarantee that a scan performed on one document,
-/// will yield a result that will work for another document.
-fn scan<D>(xml: D) -> Result<u32, XFlateError>
-where
-    D: Read,
-{
-    let buf = BufReader::new(xml);
-    let config = ParserConfig::new();
-    let parser = config.create_reader(buf);
-
-    let mut symbols: HashSet<char> = HashSet::new();
-    for e in parser {
-        match e {
-            Ok(XmlEvent::Characters(data)) => {
-                for c in data.chars() {
-                    if !symbols.contains(&c) {
-                        symbols.insert(c);
-                    }
-                }
-            }
-            Ok(XmlEvent::Whitespace(data)) => {
-                for c in data.chars() {
-                    if !symbols.contains(&c) {
-                        symbols.insert(c);
-                    }
-                }
-            }
-            Ok(XmlEvent::StartElement {
-                name: _,
-                attributes,
-                namespace: _,
-            }) => {
-                for attr in attributes {
-                    for c in attr.value.chars() {
-                        if !symbols.contains(&c) {
-                            symbols.insert(c);
-                        }
-                    }
-                }
-            }
-            Ok(XmlEvent::CData(_)) => {
-                panic!("CData not implemented yet")
-            }
-            Ok(XmlEvent::Comment(_)) => {
-                panic!("Comment not implemented yet")
-            }
-            _ => continue,
-        };
-    }
-
-    symbols
-        .len()
-        .try_into()
-        .map_err(|e| XFlateError::PrePassError(format!("Failed to count unique symbols: {}", e)))
-}
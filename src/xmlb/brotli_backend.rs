@@ -0,0 +1,51 @@
+use std::io::Write;
+
+use crate::{
+    XFlateError, XmlB, XmlBCompress, XmlBCompressor, XmlBDecompressor, XmlS,
+    xmlb::{read_limited, read_prefix},
+};
+
+/// Brotli qualities for each `XmlBCompress` level from 0 to 9
+const BROTLI_QUALITIES: [u32; 10] = [0, 1, 2, 3, 4, 5, 6, 8, 10, 11];
+
+/// Base two logarithm of the brotli window size
+const BROTLI_WINDOW_BITS: u32 = 22;
+
+pub struct XmlBBrotliBackend {
+    opt: XmlBCompress,
+}
+
+impl XmlBBrotliBackend {
+    pub fn new(opt: XmlBCompress) -> Self {
+        XmlBBrotliBackend { opt }
+    }
+}
+
+impl XmlBCompressor for XmlBBrotliBackend {
+    fn compress(&self, buf: &[u8]) -> Result<XmlB, XFlateError> {
+        let quality = BROTLI_QUALITIES[self.opt.level()? as usize];
+
+        let mut encoder =
+            brotli::CompressorWriter::new(Vec::new(), 4096, quality, BROTLI_WINDOW_BITS);
+        encoder
+            .write_all(buf)
+            .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+
+        // Finishes the stream
+        Ok(encoder.into_inner())
+    }
+}
+
+impl XmlBDecompressor for XmlBBrotliBackend {
+    fn decompress(&self, buf: &[u8]) -> Result<XmlS, XFlateError> {
+        self.decompress_limited(buf, usize::MAX)
+    }
+
+    fn decompress_limited(&self, buf: &[u8], limit: usize) -> Result<XmlS, XFlateError> {
+        read_limited(brotli::Decompressor::new(buf, 4096), limit)
+    }
+
+    fn decompress_prefix(&self, buf: &[u8], len: usize) -> Result<XmlS, XFlateError> {
+        read_prefix(brotli::Decompressor::new(buf, 4096), len)
+    }
+}
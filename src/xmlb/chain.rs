@@ -0,0 +1,73 @@
+use crate::{XFlateError, XmlB, XmlBCodec, XmlBCompressor, XmlBDecompressor, XmlS};
+
+/// Backend applying several stages in sequence.
+///
+/// Compression runs the stages in the order they were added,
+/// and decompression reverses them. A chain is itself a backend,
+/// so chains can be nested.
+///
+/// ```
+/// use xflate::{XmlBChain, XmlBCompress, XmlBDeflateBackend, XmlBMoveToFront, XmlBRunLength};
+///
+/// let chain = XmlBChain::new()
+///     .then(XmlBMoveToFront)
+///     .then(XmlBRunLength)
+///     .then(XmlBDeflateBackend::new(XmlBCompress::Best));
+/// ```
+#[derive(Default)]
+pub struct XmlBChain {
+    stages: Vec<Box<dyn XmlBCodec>>,
+}
+
+impl XmlBChain {
+    pub fn new() -> Self {
+        XmlBChain { stages: Vec::new() }
+    }
+
+    /// Append a stage to the end of the chain
+    pub fn then<C: XmlBCodec + 'static>(mut self, stage: C) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Returns the number of stages in the chain
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl XmlBCompressor for XmlBChain {
+    fn compress(&self, input: &[u8]) -> Result<XmlB, XFlateError> {
+        let mut data = input.to_vec();
+        for stage in &self.stages {
+            data = stage.compress(&data)?;
+        }
+
+        Ok(data)
+    }
+}
+
+impl XmlBDecompressor for XmlBChain {
+    fn decompress(&self, input: &[u8]) -> Result<XmlS, XFlateError> {
+        let mut data = input.to_vec();
+        for stage in self.stages.iter().rev() {
+            data = stage.decompress(&data)?;
+        }
+
+        Ok(data)
+    }
+
+    /// Every stage is held to `limit`, not only the last
+    fn decompress_limited(&self, input: &[u8], limit: usize) -> Result<XmlS, XFlateError> {
+        let mut data = input.to_vec();
+        for stage in self.stages.iter().rev() {
+            data = stage.decompress_limited(&data, limit)?;
+        }
+
+        Ok(data)
+    }
+}
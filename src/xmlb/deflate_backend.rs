@@ -1,50 +1,340 @@
-use std::io::Write;
+use std::io::{Read, Write};
 
-use flate2::write::{DeflateDecoder, DeflateEncoder};
+use flate2::{
+    Compression,
+    read::{DeflateDecoder, GzDecoder, ZlibDecoder},
+    write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+};
 
-use crate::{XFlateError, XmlB, XmlBCompress, XmlBCompressor, XmlBDecompressor, XmlS};
+use crate::{
+    XFlateError, XmlB, XmlBCompress, XmlBCompressor, XmlBDecompressor, XmlS,
+    xmlb::{read_limited, read_prefix},
+};
 
+/// Container wrapped around the deflate stream.
+///
+/// The framing is stored as the first byte of the backend output,
+/// so the stream after it can be read with standard tools. The high
+/// bit of that byte is set when a preset dictionary was used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmlBDeflateFraming {
+    /// Bare deflate stream, without header or checksum
+    Raw = 0,
+    /// zlib stream, with an Adler-32 checksum
+    Zlib = 1,
+    /// gzip stream, with a CRC-32 checksum
+    Gzip = 2,
+}
+
+/// Set in the framing byte when the stream needs a preset dictionary
+const DICTIONARY_FLAG: u8 = 0x80;
+
+/// Largest useful preset dictionary, the size of the deflate window
+pub const MAX_DEFLATE_DICTIONARY_SIZE: usize = 32 * 1024;
+
+impl TryFrom<u8> for XmlBDeflateFraming {
+    type Error = XFlateError;
+
+    fn try_from(flag: u8) -> Result<Self, Self::Error> {
+        match flag {
+            0 => Ok(XmlBDeflateFraming::Raw),
+            1 => Ok(XmlBDeflateFraming::Zlib),
+            2 => Ok(XmlBDeflateFraming::Gzip),
+            _ => Err(XFlateError::XmlBError(format!(
+                "Unknown deflate framing: {}",
+                flag
+            ))),
+        }
+    }
+}
+
+/// Deflate backend, through flate2.
+///
+/// The memory level is left at zlib's default of 8, since
+/// flate2 offers no way to change it.
 pub struct XmlBDeflateBackend {
     opt: XmlBCompress,
+    framing: XmlBDeflateFraming,
+    /// Base two logarithm of the window size, from 9 to 15.
+    /// Only supported with a zlib implementation, i.e. the `fast` feature.
+    window_bits: Option<u8>,
+    /// Preset dictionary, used to prime the deflate window.
+    /// Only supported with a zlib implementation, i.e. the `fast` feature.
+    dictionary: Option<Vec<u8>>,
 }
 
 impl XmlBDeflateBackend {
     pub fn new(opt: XmlBCompress) -> Self {
-        XmlBDeflateBackend { opt }
+        XmlBDeflateBackend::with_options(opt, XmlBDeflateFraming::Raw, None)
+    }
+
+    pub fn with_options(
+        opt: XmlBCompress,
+        framing: XmlBDeflateFraming,
+        window_bits: Option<u8>,
+    ) -> Self {
+        XmlBDeflateBackend {
+            opt,
+            framing,
+            window_bits,
+            dictionary: None,
+        }
+    }
+
+    /// Use a preset dictionary when compressing and decompressing.
+    /// Data ending up in the deflate window is most useful when it
+    /// resembles the XMLS data being compressed. Only the last
+    /// `MAX_DEFLATE_DICTIONARY_SIZE` bytes are used.
+    pub fn with_dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        let start = dictionary.len().saturating_sub(MAX_DEFLATE_DICTIONARY_SIZE);
+        self.dictionary = Some(dictionary[start..].to_vec());
+        self
     }
 }
 
 impl XmlBCompressor for XmlBDeflateBackend {
     fn compress(&self, buf: &[u8]) -> Result<XmlB, XFlateError> {
-        let mut encoder = DeflateEncoder::new(
-            Vec::new(),
-            match self.opt {
-                XmlBCompress::None => flate2::Compression::none(),
-                XmlBCompress::Fast => flate2::Compression::fast(),
-                XmlBCompress::Best => flate2::Compression::best(),
-            },
-        );
+        let level = Compression::new(self.opt.level()?);
+
+        if self.window_bits.is_some() || self.dictionary.is_some() {
+            let flag = match self.dictionary {
+                Some(_) => self.framing as u8 | DICTIONARY_FLAG,
+                None => self.framing as u8,
+            };
 
-        encoder
-            .write_all(buf)
-            .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+            return zlib::compress(
+                buf,
+                vec![flag],
+                level,
+                self.framing,
+                self.window_bits,
+                self.dictionary.as_deref(),
+            );
+        }
 
-        encoder
-            .finish()
-            .map_err(|e| XFlateError::XmlBError(e.to_string()))
+        let out = vec![self.framing as u8];
+
+        match self.framing {
+            XmlBDeflateFraming::Raw => {
+                let encoder = DeflateEncoder::new(out, level);
+                finish_writer(encoder, buf, |e| e.finish())
+            }
+            XmlBDeflateFraming::Zlib => {
+                let encoder = ZlibEncoder::new(out, level);
+                finish_writer(encoder, buf, |e| e.finish())
+            }
+            XmlBDeflateFraming::Gzip => {
+                let encoder = GzEncoder::new(out, level);
+                finish_writer(encoder, buf, |e| e.finish())
+            }
+        }
     }
 }
 
 impl XmlBDecompressor for XmlBDeflateBackend {
     fn decompress(&self, buf: &[u8]) -> Result<XmlS, XFlateError> {
-        let mut decoder = DeflateDecoder::new(Vec::new());
+        self.decompress_limited(buf, usize::MAX)
+    }
+
+    fn decompress_limited(&self, buf: &[u8], limit: usize) -> Result<XmlS, XFlateError> {
+        self.decompress_to(buf, limit, false)
+    }
+
+    fn decompress_prefix(&self, buf: &[u8], len: usize) -> Result<XmlS, XFlateError> {
+        self.decompress_to(buf, len, true)
+    }
+}
 
-        decoder
-            .write_all(buf)
-            .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+impl XmlBDeflateBackend {
+    /// Decompress up to `limit` bytes. Past that, the output is cut
+    /// off if `truncate` is set, and decompression fails otherwise.
+    fn decompress_to(&self, buf: &[u8], limit: usize, truncate: bool) -> Result<XmlS, XFlateError> {
+        let (&flag, buf) = buf.split_first().ok_or(XFlateError::XmlBError(
+            "Missing deflate framing".to_string(),
+        ))?;
+
+        if flag & DICTIONARY_FLAG != 0 {
+            let framing = XmlBDeflateFraming::try_from(flag & !DICTIONARY_FLAG)?;
+            let dictionary = self.dictionary.as_deref().ok_or(XFlateError::XmlBError(
+                "Data was compressed with a preset dictionary, but none was given".to_string(),
+            ))?;
+
+            return zlib::decompress(buf, framing, dictionary, limit, truncate);
+        }
+
+        // Any window size can be decoded with the largest window
+        let decoder: Box<dyn Read> = match XmlBDeflateFraming::try_from(flag)? {
+            XmlBDeflateFraming::Raw => Box::new(DeflateDecoder::new(buf)),
+            XmlBDeflateFraming::Zlib => Box::new(ZlibDecoder::new(buf)),
+            XmlBDeflateFraming::Gzip => Box::new(GzDecoder::new(buf)),
+        };
+
+        if truncate {
+            read_prefix(decoder, limit)
+        } else {
+            read_limited(decoder, limit)
+        }
+    }
+}
+
+/// Write all of `buf` through a flate2 writer and finish it
+fn finish_writer<W, F>(mut writer: W, buf: &[u8], finish: F) -> Result<Vec<u8>, XFlateError>
+where
+    W: Write,
+    F: FnOnce(W) -> std::io::Result<Vec<u8>>,
+{
+    writer
+        .write_all(buf)
+        .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+
+    finish(writer).map_err(|e| XFlateError::XmlBError(e.to_string()))
+}
+
+/// Deflate through flate2's zlib bindings, which are needed
+/// for window sizes and preset dictionaries.
+#[cfg(feature = "fast")]
+mod zlib {
+    use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+    use super::XmlBDeflateFraming;
+    use crate::{XFlateError, XmlB, XmlS, limits::check_payload};
+
+    pub fn compress(
+        buf: &[u8],
+        mut out: Vec<u8>,
+        level: Compression,
+        framing: XmlBDeflateFraming,
+        window_bits: Option<u8>,
+        dictionary: Option<&[u8]>,
+    ) -> Result<XmlB, XFlateError> {
+        let window_bits = window_bits.unwrap_or(15);
+        if !(9..=15).contains(&window_bits) {
+            return Err(XFlateError::XmlBError(format!(
+                "Invalid window bits {}, expected 9 to 15",
+                window_bits
+            )));
+        }
+
+        let mut compress = match framing {
+            XmlBDeflateFraming::Raw => Compress::new_with_window_bits(level, false, window_bits),
+            XmlBDeflateFraming::Zlib => Compress::new_with_window_bits(level, true, window_bits),
+            XmlBDeflateFraming::Gzip => Compress::new_gzip(level, window_bits),
+        };
+
+        if let Some(dictionary) = dictionary {
+            if framing == XmlBDeflateFraming::Gzip {
+                return Err(XFlateError::XmlBError(
+                    "gzip framing does not support preset dictionaries".to_string(),
+                ));
+            }
+
+            compress
+                .set_dictionary(dictionary)
+                .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+        }
+
+        loop {
+            out.reserve(buf.len() / 2 + 64);
+
+            let consumed = compress.total_in() as usize;
+            let status = compress
+                .compress_vec(&buf[consumed..], &mut out, FlushCompress::Finish)
+                .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+
+            if status == Status::StreamEnd {
+                return Ok(out);
+            }
+        }
+    }
+
+    pub fn decompress(
+        buf: &[u8],
+        framing: XmlBDeflateFraming,
+        dictionary: &[u8],
+        limit: usize,
+        truncate: bool,
+    ) -> Result<XmlS, XFlateError> {
+        let mut decompress = match framing {
+            XmlBDeflateFraming::Raw => Decompress::new(false),
+            XmlBDeflateFraming::Zlib => Decompress::new(true),
+            XmlBDeflateFraming::Gzip => {
+                return Err(XFlateError::XmlBError(
+                    "gzip framing does not support preset dictionaries".to_string(),
+                ));
+            }
+        };
+
+        // zlib streams ask for the dictionary once their header is read
+        if framing == XmlBDeflateFraming::Raw {
+            decompress
+                .set_dictionary(dictionary)
+                .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+        }
+
+        let mut out = Vec::with_capacity(buf.len() * 2);
+        loop {
+            out.reserve(buf.len() + 64);
+
+            let consumed = decompress.total_in() as usize;
+            let status =
+                decompress.decompress_vec(&buf[consumed..], &mut out, FlushDecompress::Finish);
+            if truncate && out.len() >= limit {
+                out.truncate(limit);
+                return Ok(out);
+            }
+            check_payload(out.len(), limit)?;
+
+            match status {
+                Ok(Status::StreamEnd) => return Ok(out),
+                Ok(_)
+                    if decompress.total_in() as usize == buf.len()
+                        && out.len() < out.capacity() =>
+                {
+                    return Err(XFlateError::XmlBError(
+                        "Unexpected end of deflate stream".to_string(),
+                    ));
+                }
+                Ok(_) => continue,
+                Err(e) if e.needs_dictionary().is_some() => {
+                    decompress
+                        .set_dictionary(dictionary)
+                        .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+                }
+                Err(e) => return Err(XFlateError::XmlBError(e.to_string())),
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "fast"))]
+mod zlib {
+    use flate2::Compression;
+
+    use super::XmlBDeflateFraming;
+    use crate::{XFlateError, XmlB, XmlS};
+
+    pub fn compress(
+        _buf: &[u8],
+        _out: Vec<u8>,
+        _level: Compression,
+        _framing: XmlBDeflateFraming,
+        _window_bits: Option<u8>,
+        _dictionary: Option<&[u8]>,
+    ) -> Result<XmlB, XFlateError> {
+        Err(XFlateError::XmlBError(
+            "Window sizes and preset dictionaries require the `fast` feature".to_string(),
+        ))
+    }
 
-        decoder
-            .finish()
-            .map_err(|e| XFlateError::XmlBError(e.to_string()))
+    pub fn decompress(
+        _buf: &[u8],
+        _framing: XmlBDeflateFraming,
+        _dictionary: &[u8],
+        _limit: usize,
+        _truncate: bool,
+    ) -> Result<XmlS, XFlateError> {
+        Err(XFlateError::XmlBError(
+            "Preset dictionaries require the `fast` feature".to_string(),
+        ))
     }
 }
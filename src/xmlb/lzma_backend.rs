@@ -0,0 +1,48 @@
+use std::io::Write;
+
+use xz2::{read::XzDecoder, write::XzEncoder};
+
+use crate::{
+    XFlateError, XmlB, XmlBCompress, XmlBCompressor, XmlBDecompressor, XmlS,
+    xmlb::{read_limited, read_prefix},
+};
+
+/// Compresses to the xz container using LZMA2.
+/// `XmlBCompress` levels map directly onto xz presets 0 to 9.
+pub struct XmlBLzmaBackend {
+    opt: XmlBCompress,
+}
+
+impl XmlBLzmaBackend {
+    pub fn new(opt: XmlBCompress) -> Self {
+        XmlBLzmaBackend { opt }
+    }
+}
+
+impl XmlBCompressor for XmlBLzmaBackend {
+    fn compress(&self, buf: &[u8]) -> Result<XmlB, XFlateError> {
+        let mut encoder = XzEncoder::new(Vec::new(), self.opt.level()?);
+
+        encoder
+            .write_all(buf)
+            .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+
+        encoder
+            .finish()
+            .map_err(|e| XFlateError::XmlBError(e.to_string()))
+    }
+}
+
+impl XmlBDecompressor for XmlBLzmaBackend {
+    fn decompress(&self, buf: &[u8]) -> Result<XmlS, XFlateError> {
+        self.decompress_limited(buf, usize::MAX)
+    }
+
+    fn decompress_limited(&self, buf: &[u8], limit: usize) -> Result<XmlS, XFlateError> {
+        read_limited(XzDecoder::new(buf), limit)
+    }
+
+    fn decompress_prefix(&self, buf: &[u8], len: usize) -> Result<XmlS, XFlateError> {
+        read_prefix(XzDecoder::new(buf), len)
+    }
+}
@@ -1,5 +1,22 @@
+#[cfg(feature = "brotli")]
+mod brotli_backend;
+mod chain;
 mod deflate_backend;
+#[cfg(feature = "lzma")]
+mod lzma_backend;
+mod transform;
+#[allow(clippy::module_inception)]
 mod xmlb;
+#[cfg(feature = "zstd")]
+mod zstd_backend;
 
+#[cfg(feature = "brotli")]
+pub use brotli_backend::*;
+pub use chain::*;
 pub use deflate_backend::*;
+#[cfg(feature = "lzma")]
+pub use lzma_backend::*;
+pub use transform::*;
 pub use xmlb::*;
+#[cfg(feature = "zstd")]
+pub use zstd_backend::*;
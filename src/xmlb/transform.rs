@@ -0,0 +1,103 @@
+use crate::{XFlateError, XmlB, XmlBCompressor, XmlBDecompressor, XmlS};
+
+/// Move-to-front transform.
+///
+/// Each byte is replaced by its position in a list of recently
+/// seen bytes, which is then moved to the front. Data with local
+/// repetition turns into runs of small values. The output is the
+/// same size as the input, so it's meant to be used in an
+/// `XmlBChain` ahead of an actual compressor.
+pub struct XmlBMoveToFront;
+
+impl XmlBCompressor for XmlBMoveToFront {
+    fn compress(&self, input: &[u8]) -> Result<XmlB, XFlateError> {
+        let mut order: Vec<u8> = (0..=255).collect();
+
+        Ok(input
+            .iter()
+            .map(|&byte| {
+                let pos = order.iter().position(|&b| b == byte).unwrap_or_default();
+                order.remove(pos);
+                order.insert(0, byte);
+                pos as u8
+            })
+            .collect())
+    }
+}
+
+impl XmlBDecompressor for XmlBMoveToFront {
+    fn decompress(&self, input: &[u8]) -> Result<XmlS, XFlateError> {
+        let mut order: Vec<u8> = (0..=255).collect();
+
+        Ok(input
+            .iter()
+            .map(|&pos| {
+                let byte = order.remove(pos as usize);
+                order.insert(0, byte);
+                byte
+            })
+            .collect())
+    }
+}
+
+/// Run-length encoding, as done by bzip2.
+///
+/// Runs of four identical bytes are followed by a count byte
+/// holding the number of further repetitions, up to 255.
+/// Data without runs passes through unchanged.
+pub struct XmlBRunLength;
+
+/// Length of a run before a count byte is written
+const RUN_LENGTH: usize = 4;
+
+impl XmlBCompressor for XmlBRunLength {
+    fn compress(&self, input: &[u8]) -> Result<XmlB, XFlateError> {
+        let mut encoded = Vec::with_capacity(input.len());
+        let mut i = 0;
+
+        while i < input.len() {
+            let byte = input[i];
+            let run = input[i..]
+                .iter()
+                .take(RUN_LENGTH + u8::MAX as usize)
+                .take_while(|&&b| b == byte)
+                .count();
+
+            encoded.extend(std::iter::repeat_n(byte, run.min(RUN_LENGTH)));
+            if run >= RUN_LENGTH {
+                encoded.push((run - RUN_LENGTH) as u8);
+            }
+
+            i += run;
+        }
+
+        Ok(encoded)
+    }
+}
+
+impl XmlBDecompressor for XmlBRunLength {
+    fn decompress(&self, input: &[u8]) -> Result<XmlS, XFlateError> {
+        let mut decoded = Vec::with_capacity(input.len());
+        let mut bytes = input.iter().copied();
+        let mut last = None;
+        let mut run = 0;
+
+        while let Some(byte) = bytes.next() {
+            run = if last == Some(byte) { run + 1 } else { 1 };
+            last = Some(byte);
+            decoded.push(byte);
+
+            if run == RUN_LENGTH {
+                let count = bytes.next().ok_or(XFlateError::XmlBError(
+                    "Run length is missing its count".to_string(),
+                ))?;
+                decoded.extend(std::iter::repeat_n(byte, count as usize));
+
+                last = None;
+                run = 0;
+            }
+        }
+
+        Ok(decoded)
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{XFlateError, XmlS};
+use std::{fmt::Display, io::Read, str::FromStr};
+
+use crate::{XFlateError, XmlS, limits::check_payload};
 
 pub type XmlB = Vec<u8>;
 
@@ -13,6 +15,29 @@ pub enum XmlBCompress {
     Fast,
     /// Best compression
     Best,
+    /// Explicit compression level, from 0 (none) to 9 (best)
+    Level(u32),
+}
+
+impl XmlBCompress {
+    /// Highest level accepted by `XmlBCompress::Level`
+    pub const MAX_LEVEL: u32 = 9;
+
+    /// Returns the compression level on a scale from 0 to 9.
+    /// Backends with a different scale map from this one.
+    pub fn level(self) -> Result<u32, XFlateError> {
+        match self {
+            XmlBCompress::None => Ok(0),
+            XmlBCompress::Fast => Ok(1),
+            XmlBCompress::Best => Ok(XmlBCompress::MAX_LEVEL),
+            XmlBCompress::Level(level) if level <= XmlBCompress::MAX_LEVEL => Ok(level),
+            XmlBCompress::Level(level) => Err(XFlateError::XmlBError(format!(
+                "Invalid compression level {}, expected 0 to {}",
+                level,
+                XmlBCompress::MAX_LEVEL
+            ))),
+        }
+    }
 }
 
 /// Implementors of this trait may act as backend compressors
@@ -27,15 +52,159 @@ pub trait XmlBCompressor {
 pub trait XmlBDecompressor {
     /// Decompresses data into XMLN encoding
     fn decompress(&self, input: &[u8]) -> Result<XmlS, XFlateError>;
+
+    /// Decompresses data, failing if the output is larger than `limit` bytes.
+    ///
+    /// The default decompresses everything before checking the size.
+    /// Backends able to stop early override it, so that highly
+    /// compressed data can't take up more memory than the limit.
+    fn decompress_limited(&self, input: &[u8], limit: usize) -> Result<XmlS, XFlateError> {
+        let output = self.decompress(input)?;
+        check_payload(output.len(), limit)?;
+
+        Ok(output)
+    }
+
+    /// Decompresses the first `len` bytes of the output, or all
+    /// of it if the output is shorter.
+    ///
+    /// The default decompresses everything before truncating.
+    /// Backends able to stop early override it.
+    fn decompress_prefix(&self, input: &[u8], len: usize) -> Result<XmlS, XFlateError> {
+        let mut output = self.decompress(input)?;
+        output.truncate(len);
+
+        Ok(output)
+    }
+}
+
+/// Backends able to both compress and decompress.
+/// This is implemented for every such type.
+pub trait XmlBCodec: XmlBCompressor + XmlBDecompressor {}
+
+impl<T: XmlBCompressor + XmlBDecompressor> XmlBCodec for T {}
+
+/// Backend compression algorithms available to xflate.
+///
+/// The discriminant is stored in the compressed output, so that
+/// `XFlate::decompress` can pick the matching backend. Backends
+/// other than deflate are enabled by the cargo feature of the
+/// same name, with `lzma` providing the xz format. Every variant
+/// exists in every build; compressing or decompressing with one
+/// whose feature is disabled returns an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmlBBackend {
+    Deflate = 0,
+    Zstd = 1,
+    Brotli = 2,
+    Lzma = 3,
+}
+
+impl XmlBBackend {
+    /// All backends enabled in this build
+    pub const ALL: &[XmlBBackend] = &[
+        XmlBBackend::Deflate,
+        #[cfg(feature = "zstd")]
+        XmlBBackend::Zstd,
+        #[cfg(feature = "brotli")]
+        XmlBBackend::Brotli,
+        #[cfg(feature = "lzma")]
+        XmlBBackend::Lzma,
+    ];
+}
+
+impl TryFrom<u8> for XmlBBackend {
+    type Error = XFlateError;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(XmlBBackend::Deflate),
+            1 => Ok(XmlBBackend::Zstd),
+            2 => Ok(XmlBBackend::Brotli),
+            3 => Ok(XmlBBackend::Lzma),
+            _ => Err(XFlateError::XmlBError(format!("Unknown backend: {}", id))),
+        }
+    }
+}
+
+impl XmlBBackend {
+    /// Name of the backend, as accepted by `from_str`
+    pub fn name(self) -> &'static str {
+        match self {
+            XmlBBackend::Deflate => "deflate",
+            XmlBBackend::Zstd => "zstd",
+            XmlBBackend::Brotli => "brotli",
+            XmlBBackend::Lzma => "lzma",
+        }
+    }
 }
 
-pub fn encode_xmlb<D: XmlBCompressor>(data: &[u8], compressor: &D) -> Result<XmlB, XFlateError> {
+impl FromStr for XmlBBackend {
+    type Err = XFlateError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match XmlBBackend::ALL.iter().find(|b| b.name() == name) {
+            Some(&backend) => Ok(backend),
+            None => match name {
+                "zstd" | "brotli" | "lzma" => Err(missing_backend(name)),
+                _ => Err(XFlateError::XmlBError(format!("Unknown backend: {}", name))),
+            },
+        }
+    }
+}
+
+impl Display for XmlBBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Error for a backend whose feature is disabled in this build
+pub(crate) fn missing_backend(name: &str) -> XFlateError {
+    XFlateError::XmlBError(format!("Backend {} requires the `{}` feature", name, name))
+}
+
+pub fn encode_xmlb<D: XmlBCompressor + ?Sized>(
+    data: &[u8],
+    compressor: &D,
+) -> Result<XmlB, XFlateError> {
     compressor.compress(data)
 }
 
-pub fn decode_xmlb<D: XmlBDecompressor>(
+pub fn decode_xmlb<D: XmlBDecompressor + ?Sized>(
     data: &[u8],
     decompressor: &D,
 ) -> Result<XmlS, XFlateError> {
     decompressor.decompress(data)
 }
+
+pub fn decode_xmlb_limited<D: XmlBDecompressor + ?Sized>(
+    data: &[u8],
+    decompressor: &D,
+    limit: usize,
+) -> Result<XmlS, XFlateError> {
+    decompressor.decompress_limited(data, limit)
+}
+
+/// Read a decoder to the end, failing once more than `limit` bytes come out
+pub(crate) fn read_limited<R: Read>(reader: R, limit: usize) -> Result<XmlS, XFlateError> {
+    let mut output = Vec::new();
+    reader
+        .take((limit as u64).saturating_add(1))
+        .read_to_end(&mut output)
+        .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+    check_payload(output.len(), limit)?;
+
+    Ok(output)
+}
+
+/// Read no more than the first `len` bytes out of a decoder
+pub(crate) fn read_prefix<R: Read>(reader: R, len: usize) -> Result<XmlS, XFlateError> {
+    let mut output = Vec::new();
+    reader
+        .take(len as u64)
+        .read_to_end(&mut output)
+        .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+
+    Ok(output)
+}
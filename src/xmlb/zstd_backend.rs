@@ -0,0 +1,46 @@
+use crate::{
+    XFlateError, XmlB, XmlBCompress, XmlBCompressor, XmlBDecompressor, XmlS,
+    xmlb::{read_limited, read_prefix},
+};
+
+/// zstd levels for each `XmlBCompress` level from 0 to 9.
+/// zstd has no uncompressed mode, so level 0 maps to its fastest level.
+const ZSTD_LEVELS: [i32; 10] = [1, 1, 3, 5, 7, 9, 12, 15, 19, 22];
+
+pub struct XmlBZstdBackend {
+    opt: XmlBCompress,
+}
+
+impl XmlBZstdBackend {
+    pub fn new(opt: XmlBCompress) -> Self {
+        XmlBZstdBackend { opt }
+    }
+}
+
+impl XmlBCompressor for XmlBZstdBackend {
+    fn compress(&self, buf: &[u8]) -> Result<XmlB, XFlateError> {
+        let level = ZSTD_LEVELS[self.opt.level()? as usize];
+
+        zstd::bulk::compress(buf, level).map_err(|e| XFlateError::XmlBError(e.to_string()))
+    }
+}
+
+impl XmlBDecompressor for XmlBZstdBackend {
+    fn decompress(&self, buf: &[u8]) -> Result<XmlS, XFlateError> {
+        zstd::stream::decode_all(buf).map_err(|e| XFlateError::XmlBError(e.to_string()))
+    }
+
+    fn decompress_limited(&self, buf: &[u8], limit: usize) -> Result<XmlS, XFlateError> {
+        let decoder = zstd::stream::read::Decoder::new(buf)
+            .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+
+        read_limited(decoder, limit)
+    }
+
+    fn decompress_prefix(&self, buf: &[u8], len: usize) -> Result<XmlS, XFlateError> {
+        let decoder = zstd::stream::read::Decoder::new(buf)
+            .map_err(|e| XFlateError::XmlBError(e.to_string()))?;
+
+        read_prefix(decoder, len)
+    }
+}
@@ -0,0 +1,100 @@
+use xml::{ParserConfig, reader::XmlEvent};
+
+use crate::{
+    XFlateError,
+    xmln::header::{bytes_header, split_bytes_header},
+};
+
+/// Stands in for the '&' of an entity reference kept in the text.
+/// This is a noncharacter, so a well-formed document can't hold it.
+/// Events are checked for it, as a Rust string can.
+pub(crate) const ENTITY_REFERENCE: char = '\u{FFFF}';
+
+/// How references to entities declared in the document type are encoded.
+/// References to the predefined entities, such as `&amp;`, are always expanded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum XmlNEntities {
+    /// Replace references with the value of the entity
+    #[default]
+    Expand,
+    /// Keep references as written, such as `&copy;`
+    Preserve,
+}
+
+/// Returns the document type declaration of the XML, if it has one,
+/// including any internal subset. Parsing stops at the root element.
+pub fn read_doctype(xml: &[u8]) -> Result<Option<String>, XFlateError> {
+    let mut parser = ParserConfig::new().create_reader(xml);
+
+    loop {
+        match parser.next() {
+            Ok(XmlEvent::StartElement { .. }) | Ok(XmlEvent::EndDocument) => {
+                return Ok(parser.doctype().map(str::to_string));
+            }
+            Ok(_) => continue,
+            Err(e) => {
+                return Err(XFlateError::XmlNError(format!("XML parsing error: {}", e)));
+            }
+        }
+    }
+}
+
+/// Parser configuration for the XML. To preserve entity references,
+/// each entity declared in the document type expands to a marked
+/// copy of its own reference instead.
+pub(crate) fn parser_config(
+    xml: &[u8],
+    entities: XmlNEntities,
+) -> Result<ParserConfig, XFlateError> {
+    let config = ParserConfig::new();
+
+    if entities == XmlNEntities::Expand {
+        return Ok(config);
+    }
+
+    let Some(doctype) = read_doctype(xml)? else {
+        return Ok(config);
+    };
+
+    Ok(entity_names(&doctype)
+        .into_iter()
+        .fold(config, |config, name| {
+            let reference = format!("{}{};", ENTITY_REFERENCE, name);
+            config.add_entity(name, reference)
+        }))
+}
+
+/// Names of the general entities declared in a document type
+fn entity_names(doctype: &str) -> Vec<&str> {
+    doctype
+        .match_indices("<!ENTITY")
+        .filter_map(|(i, declaration)| {
+            let rest = doctype[i + declaration.len()..].trim_start();
+
+            // Parameter entities can only be referenced in the DTD
+            if rest.starts_with('%') {
+                return None;
+            }
+
+            rest.split(char::is_whitespace)
+                .next()
+                .filter(|name| !name.is_empty())
+        })
+        .collect()
+}
+
+/// Returns a header holding the doctype, to follow the table headers.
+/// <header> := D <length> <doctype>
+pub(crate) fn doctype_header(doctype: &str) -> Vec<u8> {
+    bytes_header(b'D', doctype.as_bytes())
+}
+
+/// Parse a header written by `doctype_header`.
+/// Returns the doctype and the bytes following the header.
+pub(crate) fn split_doctype_header(bytes: &[u8]) -> Result<(&str, &[u8]), XFlateError> {
+    let (doctype, rest) = split_bytes_header(bytes, b'D', "doctype")?;
+    let doctype = std::str::from_utf8(doctype)
+        .map_err(|e| XFlateError::FormatError(format!("Invalid doctype header: {}", e)))?;
+
+    Ok((doctype, rest))
+}
@@ -0,0 +1,370 @@
+use std::{collections::VecDeque, ops::Range};
+
+use xml::{
+    attribute::OwnedAttribute,
+    common::XmlVersion,
+    name::OwnedName,
+    namespace::{NS_NO_PREFIX, NS_XMLNS_PREFIX, Namespace, NamespaceStack},
+    reader::XmlEvent,
+};
+
+use crate::{
+    XFlateError, XFlateLimits, XmlNSymbolTable, XmlNTagTable, split_codes,
+    xmln::{doctype::ENTITY_REFERENCE, whitespace::Indentation},
+};
+
+/// Iterator over the events of an XMLN document, decoded one token
+/// at a time. See `decode_xmln_events`.
+pub struct XmlNEvents<S, T> {
+    xmln: String,
+    /// Byte offset of the next field
+    pos: usize,
+    sym_table: S,
+    tag_table: T,
+    limits: XFlateLimits,
+    /// Open elements, with the namespaces in scope in each
+    stack: Vec<(OwnedName, Namespace)>,
+    /// Declared by the first indentation token
+    indentation: Option<Indentation>,
+    /// Events decoded ahead of time
+    queued: VecDeque<XmlEvent>,
+    /// Bytes of XML the events decoded so far stand for
+    output: usize,
+    finished: bool,
+}
+
+/// Decode XMLN into the events a parser reads from `decode_xmln`
+/// output, without rendering the text. Tokens are decoded as the
+/// events are read, so stopping early skips the rest of the work.
+///
+/// Namespace declarations are resolved as a parser would. References
+/// kept by `XmlNEntities::Preserve` are left in the text as written,
+/// such as `&copy;`. Decoding stops at the first error, which
+/// includes exceeding the limits on the size of the XML the events
+/// stand for, and on depth.
+pub fn decode_xmln_events<S, T>(
+    xmln: String,
+    sym_table: S,
+    tag_table: T,
+    limits: &XFlateLimits,
+) -> XmlNEvents<S, T>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    let start = XmlEvent::StartDocument {
+        version: XmlVersion::Version10,
+        encoding: "UTF-8".to_string(),
+        standalone: None,
+    };
+
+    XmlNEvents {
+        xmln,
+        pos: 0,
+        sym_table,
+        tag_table,
+        limits: *limits,
+        stack: Vec::new(),
+        indentation: None,
+        queued: VecDeque::from([start]),
+        output: 0,
+        finished: false,
+    }
+}
+
+impl<S, T> Iterator for XmlNEvents<S, T>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    type Item = Result<XmlEvent, XFlateError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.queued.pop_front() {
+            return Some(Ok(event));
+        }
+
+        if self.finished {
+            return None;
+        }
+
+        let event = self.next_event();
+        if event.is_err() {
+            self.finished = true;
+        }
+
+        Some(event)
+    }
+}
+
+impl<S, T> XmlNEvents<S, T>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    fn next_event(&mut self) -> Result<XmlEvent, XFlateError> {
+        let range = loop {
+            match self.next_field() {
+                Some(range) if range.is_empty() => continue,
+                Some(range) => break range,
+                None => return self.end_document(),
+            }
+        };
+        let token = &self.xmln[range.clone()];
+
+        match token.as_bytes()[0] {
+            b'T' => {
+                let code = parse_code(&token[1..], "tag")?;
+                self.start_element(code)
+            }
+            b'0' if token == "0" => self.end_element(),
+            b'I' | b'0'..=b'9' => self.text(range),
+            b'A' => Err(XFlateError::XmlNError(
+                "Attribute must follow a start tag".to_string(),
+            )),
+            b'S' => Err(XFlateError::XmlNError(
+                "Self-closing tag must follow a start tag".to_string(),
+            )),
+            _ => Err(XFlateError::XmlNError(format!(
+                "Unexpected token in XMLN: {}",
+                token
+            ))),
+        }
+    }
+
+    /// Read a start tag and its attributes. The namespaces they
+    /// declare are resolved, and taken out of the attributes.
+    fn start_element(&mut self, code: u16) -> Result<XmlEvent, XFlateError> {
+        let tag = self.decode_tag(code)?;
+
+        let mut namespace = match self.stack.last() {
+            Some((_, namespace)) => namespace.clone(),
+            None => NamespaceStack::default().squash(),
+        };
+        let mut attributes = Vec::new();
+
+        while let Some(range) = self.next_field_if(|field| field.starts_with('A')) {
+            let code = parse_code(&self.xmln[range][1..], "attribute")?;
+            let name = self.decode_tag(code)?;
+            let value = match self.next_field() {
+                Some(range) => decode_symbols(&self.xmln[range], &self.sym_table)?,
+                None => String::new(),
+            };
+
+            match name.split_once(':') {
+                _ if name == NS_XMLNS_PREFIX => {
+                    namespace.force_put(NS_NO_PREFIX, value);
+                }
+                Some((NS_XMLNS_PREFIX, prefix)) => {
+                    namespace.force_put(prefix, value);
+                }
+                _ => attributes.push((name, value)),
+            }
+        }
+
+        let name = resolve(&tag, &namespace, true)?;
+        let attributes = attributes
+            .into_iter()
+            .map(|(name, value)| {
+                Ok(OwnedAttribute {
+                    name: resolve(&name, &namespace, false)?,
+                    value,
+                })
+            })
+            .collect::<Result<Vec<_>, XFlateError>>()?;
+
+        self.limits.check_depth(self.stack.len() + 1)?;
+
+        // As in `<tag attribute="value">`
+        let markup = attributes
+            .iter()
+            .map(|attr| attr.name.local_name.len() + attr.value.len() + 5)
+            .sum::<usize>();
+        self.add_output(tag.len() + markup + 2)?;
+
+        // An element without content ends right away
+        if self.next_field_if(|field| field == "S").is_some() {
+            self.queued
+                .push_back(XmlEvent::EndElement { name: name.clone() });
+        } else {
+            self.stack.push((name.clone(), namespace.clone()));
+        }
+
+        Ok(XmlEvent::StartElement {
+            name,
+            attributes,
+            namespace,
+        })
+    }
+
+    fn end_element(&mut self) -> Result<XmlEvent, XFlateError> {
+        let (name, _) = self
+            .stack
+            .pop()
+            .ok_or_else(|| XFlateError::XmlNError("Unmatched closing tag found".to_string()))?;
+
+        // As in `</tag>`
+        self.add_output(
+            name.local_name.len() + name.prefix.as_ref().map_or(0, |p| p.len() + 1) + 3,
+        )?;
+
+        Ok(XmlEvent::EndElement { name })
+    }
+
+    fn end_document(&mut self) -> Result<XmlEvent, XFlateError> {
+        self.finished = true;
+
+        match self.stack.last() {
+            Some((name, _)) => Err(XFlateError::XmlNError(format!(
+                "Element {} is never closed",
+                name
+            ))),
+            None => Ok(XmlEvent::EndDocument),
+        }
+    }
+
+    /// Read a run of text and indentation tokens, which a parser
+    /// would report as a single event
+    fn text(&mut self, first: Range<usize>) -> Result<XmlEvent, XFlateError> {
+        let mut text = String::new();
+        self.push_text(first, &mut text)?;
+
+        while let Some(range) = self.next_field_if(is_text) {
+            self.push_text(range, &mut text)?;
+        }
+
+        if text.chars().all(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) {
+            Ok(XmlEvent::Whitespace(text))
+        } else {
+            Ok(XmlEvent::Characters(text))
+        }
+    }
+
+    fn push_text(&mut self, range: Range<usize>, text: &mut String) -> Result<(), XFlateError> {
+        let token = &self.xmln[range];
+
+        let Some(declaration) = token.strip_prefix('I') else {
+            let decoded = decode_symbols(token, &self.sym_table)?;
+            self.add_output(decoded.len())?;
+            text.push_str(&decoded);
+            return Ok(());
+        };
+
+        if !declaration.is_empty() {
+            let declaration = decode_symbols(declaration, &self.sym_table)?;
+            self.indentation = Some(Indentation::from_declaration(&declaration)?);
+        }
+
+        let indentation = self.indentation.clone().ok_or_else(|| {
+            XFlateError::XmlNError("Indentation used before it is declared".to_string())
+        })?;
+
+        // A closing token ends the element the whitespace is in
+        let depth = if self.peek_field() == Some("0") {
+            self.stack.len().saturating_sub(1)
+        } else {
+            self.stack.len()
+        };
+
+        // Deep indentation expands a lot, so check before allocating
+        self.add_output(indentation.len_at(depth))?;
+        text.push_str(&indentation.at(depth));
+        Ok(())
+    }
+
+    /// Count bytes of XML towards the output limit
+    fn add_output(&mut self, bytes: usize) -> Result<(), XFlateError> {
+        self.output = self.output.saturating_add(bytes);
+        self.limits.check_output(self.output)
+    }
+
+    fn decode_tag(&self, code: u16) -> Result<String, XFlateError> {
+        self.tag_table
+            .decode(code)
+            .map(str::to_string)
+            .ok_or_else(|| XFlateError::XmlNError(format!("Unknown tag code: {}", code)))
+    }
+
+    /// Next space separated field. An empty attribute value
+    /// leaves an empty field in its place.
+    fn next_field(&mut self) -> Option<Range<usize>> {
+        let range = self.field_at(self.pos)?;
+        self.pos = range.end + 1;
+        Some(range)
+    }
+
+    fn next_field_if<F>(&mut self, f: F) -> Option<Range<usize>>
+    where
+        F: FnOnce(&str) -> bool,
+    {
+        self.peek_field().filter(|field| f(field))?;
+        self.next_field()
+    }
+
+    fn peek_field(&self) -> Option<&str> {
+        self.field_at(self.pos).map(|range| &self.xmln[range])
+    }
+
+    fn field_at(&self, start: usize) -> Option<Range<usize>> {
+        let rest = self.xmln.get(start..)?;
+        let end = rest.find(' ').map_or(self.xmln.len(), |i| start + i);
+
+        Some(start..end)
+    }
+}
+
+/// Whether a field is a text or indentation token, rather than markup
+fn is_text(field: &str) -> bool {
+    match field.as_bytes().first() {
+        Some(b'I') => true,
+        Some(b'0'..=b'9') => field != "0",
+        _ => false,
+    }
+}
+
+fn parse_code(code: &str, kind: &str) -> Result<u16, XFlateError> {
+    code.parse()
+        .map_err(|_| XFlateError::XmlNError(format!("Invalid {} code: {}", kind, code)))
+}
+
+/// Decode a run of symbol codes
+fn decode_symbols<S>(codes: &str, sym_table: &S) -> Result<String, XFlateError>
+where
+    S: XmlNSymbolTable,
+{
+    let mut text = String::with_capacity(codes.len() / sym_table.code_size().max(1) as usize);
+
+    for code in split_codes(codes, sym_table.code_size())? {
+        match sym_table.decode(code) {
+            Some(ENTITY_REFERENCE) => text.push('&'),
+            Some(symbol) => text.push(symbol),
+            None => {
+                return Err(XFlateError::XmlNError(format!(
+                    "Unknown symbol code: {}",
+                    code
+                )));
+            }
+        }
+    }
+
+    Ok(text)
+}
+
+/// Resolve the prefix of a name. Unprefixed attributes have
+/// no namespace, while elements are in the default namespace.
+fn resolve(name: &str, namespace: &Namespace, element: bool) -> Result<OwnedName, XFlateError> {
+    match name.split_once(':') {
+        Some((prefix, local_name)) => {
+            let uri = namespace.get(prefix).ok_or_else(|| {
+                XFlateError::XmlNError(format!("Unbound namespace prefix: {}", prefix))
+            })?;
+
+            Ok(OwnedName::qualified(local_name, uri, Some(prefix)))
+        }
+        None if element => match namespace.get(NS_NO_PREFIX) {
+            Some(uri) if !uri.is_empty() => Ok(OwnedName::qualified(name, uri, None::<&str>)),
+            _ => Ok(OwnedName::local(name)),
+        },
+        None => Ok(OwnedName::local(name)),
+    }
+}
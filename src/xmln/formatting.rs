@@ -0,0 +1,162 @@
+use xml::common::TextPosition;
+
+use crate::{
+    XFlateError,
+    xmln::header::{bytes_header, split_bytes_header},
+};
+
+/// Side stream recording how the original document differs from
+/// what `decode_xmln` writes, so the original bytes can be restored.
+///
+/// The decoder output is split in pieces, one per XMLN token plus a
+/// leading piece for anything before the first element. The stream
+/// holds the original bytes of every piece that differs, as records
+/// of `<pieces skipped> <byte length> <bytes>` with LEB128 numbers.
+pub type XmlNFormatting = Vec<u8>;
+
+/// Record a piece, if the original differs from what the decoder writes
+pub(crate) fn push_piece(
+    formatting: &mut XmlNFormatting,
+    skipped: &mut usize,
+    original: &str,
+    decoded: &str,
+) {
+    if original == decoded {
+        *skipped += 1;
+        return;
+    }
+
+    push_varint(formatting, *skipped);
+    push_varint(formatting, original.len());
+    formatting.extend(original.as_bytes());
+    *skipped = 0;
+}
+
+/// Replace the pieces recorded in `formatting` with their original bytes
+pub(crate) fn restore<'a>(
+    pieces: impl Iterator<Item = &'a str>,
+    formatting: &[u8],
+) -> Result<String, XFlateError> {
+    let mut restored = String::new();
+    let mut pieces = pieces;
+    let mut rest = formatting;
+
+    while !rest.is_empty() {
+        let skipped = read_varint(&mut rest)?;
+        let len = read_varint(&mut rest)?;
+
+        for _ in 0..skipped {
+            let piece = pieces.next().ok_or_else(|| {
+                XFlateError::FormatError("Formatting refers past the last token".to_string())
+            })?;
+            restored.push_str(piece);
+        }
+
+        pieces.next().ok_or_else(|| {
+            XFlateError::FormatError("Formatting refers past the last token".to_string())
+        })?;
+
+        let original = rest.get(..len).ok_or_else(|| {
+            XFlateError::FormatError("Formatting record is truncated".to_string())
+        })?;
+        let original = std::str::from_utf8(original).map_err(|_| {
+            XFlateError::FormatError("Formatting record is not valid UTF-8".to_string())
+        })?;
+        restored.push_str(original);
+        rest = &rest[len..];
+    }
+
+    for piece in pieces {
+        restored.push_str(piece);
+    }
+
+    Ok(restored)
+}
+
+/// Returns a header holding the formatting, to follow the table headers.
+/// <header> := F <length> <bytes>
+pub(crate) fn formatting_header(formatting: &[u8]) -> Vec<u8> {
+    bytes_header(b'F', formatting)
+}
+
+/// Parse a header written by `formatting_header`.
+/// Returns the formatting and the bytes following the header.
+pub(crate) fn split_formatting_header(bytes: &[u8]) -> Result<(&[u8], &[u8]), XFlateError> {
+    split_bytes_header(bytes, b'F', "formatting")
+}
+
+/// Converts parser positions to byte offsets into the document.
+/// Positions must be given in increasing order.
+pub(crate) struct ByteOffsets<'a> {
+    text: &'a str,
+    offset: usize,
+    row: u64,
+    column: u64,
+}
+
+impl<'a> ByteOffsets<'a> {
+    pub fn new(text: &'a str) -> Self {
+        ByteOffsets {
+            text,
+            // The parser doesn't count a byte order mark
+            offset: text
+                .strip_prefix('\u{feff}')
+                .map_or(0, |_| '\u{feff}'.len_utf8()),
+            row: 0,
+            column: 0,
+        }
+    }
+
+    pub fn offset(&mut self, pos: TextPosition) -> usize {
+        let mut chars = self.text[self.offset..].chars().peekable();
+
+        while (self.row, self.column) < (pos.row, pos.column) {
+            let Some(c) = chars.next() else {
+                break;
+            };
+            self.offset += c.len_utf8();
+
+            // The parser counts a CRLF pair as a single line break
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                continue;
+            }
+
+            if c == '\n' || c == '\r' {
+                self.row += 1;
+                self.column = 0;
+            } else {
+                self.column += 1;
+            }
+        }
+
+        self.offset
+    }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<usize, XFlateError> {
+    let mut value: usize = 0;
+
+    for shift in (0..usize::BITS).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or_else(|| {
+            XFlateError::FormatError("Formatting record is truncated".to_string())
+        })?;
+        *bytes = rest;
+
+        value |= ((byte & 0x7F) as usize).checked_shl(shift).unwrap_or(0);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(XFlateError::FormatError(
+        "Formatting record holds too large a number".to_string(),
+    ))
+}
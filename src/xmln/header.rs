@@ -0,0 +1,118 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::XFlateError;
+
+/// Cursor over a table header, producing errors
+/// that name the header and the offending byte offset.
+pub(crate) struct HeaderReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    name: &'static str,
+}
+
+impl<'a> HeaderReader<'a> {
+    pub fn new(bytes: &'a [u8], name: &'static str) -> Self {
+        HeaderReader {
+            bytes,
+            pos: 0,
+            name,
+        }
+    }
+
+    /// Number of bytes read so far
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Bytes not read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    pub fn advance(&mut self, count: usize) {
+        self.pos += count;
+    }
+
+    pub fn error(&self, message: impl Display) -> XFlateError {
+        XFlateError::FormatError(format!(
+            "Invalid {} header at byte {}: {}",
+            self.name, self.pos, message
+        ))
+    }
+
+    /// Consume `byte`, failing if the next byte is anything else
+    pub fn expect(&mut self, byte: u8) -> Result<(), XFlateError> {
+        match self.bytes.get(self.pos) {
+            Some(&b) if b == byte => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(&b) => Err(self.error(format!(
+                "expected '{}', found '{}'",
+                byte.escape_ascii(),
+                b.escape_ascii()
+            ))),
+            None => Err(self.error(format!("expected '{}', found the end", byte.escape_ascii()))),
+        }
+    }
+
+    /// Consume a decimal number followed by a space
+    pub fn number<T: FromStr>(&mut self, what: &str) -> Result<T, XFlateError> {
+        let digits = self
+            .remaining()
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+
+        if digits == 0 {
+            return Err(self.error(format!("expected the {}", what)));
+        }
+
+        // Only ASCII digits, so this is valid UTF-8
+        let text = std::str::from_utf8(&self.remaining()[..digits]).unwrap_or_default();
+        let number = text
+            .parse()
+            .map_err(|_| self.error(format!("the {} {} is out of range", what, text)))?;
+
+        self.pos += digits;
+        self.expect(b' ')?;
+
+        Ok(number)
+    }
+}
+
+/// Returns a header holding arbitrary bytes.
+/// <header> := <marker> <length> <bytes>
+pub(crate) fn bytes_header(marker: u8, bytes: &[u8]) -> Vec<u8> {
+    let mut header = vec![marker, b' '];
+    header.extend(format!("{} ", bytes.len()).into_bytes());
+    header.extend(bytes);
+    header.push(b' ');
+
+    header
+}
+
+/// Parse a header written by `bytes_header`.
+/// Returns the bytes held and the bytes following the header.
+pub(crate) fn split_bytes_header<'a>(
+    bytes: &'a [u8],
+    marker: u8,
+    name: &'static str,
+) -> Result<(&'a [u8], &'a [u8]), XFlateError> {
+    let mut reader = HeaderReader::new(bytes, name);
+    reader.expect(marker)?;
+    reader.expect(b' ')?;
+
+    let len: usize = reader.number("length")?;
+    let held = reader.remaining().get(..len).ok_or_else(|| {
+        reader.error(format!(
+            "{} bytes can't fit in the remaining {} bytes",
+            len,
+            reader.remaining().len()
+        ))
+    })?;
+    reader.advance(len);
+    reader.expect(b' ')?;
+
+    Ok((held, reader.remaining()))
+}
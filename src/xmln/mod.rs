@@ -1,7 +1,21 @@
+mod doctype;
+mod events;
+mod formatting;
+mod header;
+mod namespace;
 mod symbol_table;
 mod tag_table;
+mod whitespace;
+#[allow(clippy::module_inception)]
 mod xmln;
 
+pub(crate) use doctype::{ENTITY_REFERENCE, doctype_header, parser_config, split_doctype_header};
+pub use doctype::{XmlNEntities, read_doctype};
+pub use events::{XmlNEvents, decode_xmln_events};
+pub use formatting::XmlNFormatting;
+pub(crate) use formatting::{formatting_header, split_formatting_header};
+pub(crate) use namespace::{declaration_name, declared_namespaces, qualified_name};
 pub use symbol_table::*;
 pub use tag_table::*;
+pub(crate) use whitespace::{Indentation, preserves_space};
 pub use xmln::*;
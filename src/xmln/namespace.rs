@@ -0,0 +1,38 @@
+use xml::{
+    name::OwnedName,
+    namespace::{NS_EMPTY_URI, NS_NO_PREFIX, NS_XML_PREFIX, NS_XMLNS_PREFIX, Namespace},
+};
+
+/// Name as written in the document, such as `xlink:href`.
+/// Tags and attribute names are stored in the tag table this way.
+pub(crate) fn qualified_name(name: &OwnedName) -> String {
+    match &name.prefix {
+        Some(prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+/// Namespaces an element declares, as `(prefix, uri)` pairs sorted by
+/// prefix. The parser only reports the namespaces in scope, so these
+/// are the ones that differ from those of the parent element.
+pub(crate) fn declared_namespaces(
+    namespace: &Namespace,
+    parent: &Namespace,
+) -> Vec<(String, String)> {
+    namespace
+        .0
+        .iter()
+        .filter(|(prefix, _)| *prefix != NS_XML_PREFIX && *prefix != NS_XMLNS_PREFIX)
+        .filter(|(prefix, uri)| parent.get(prefix.as_str()).unwrap_or(NS_EMPTY_URI) != *uri)
+        .map(|(prefix, uri)| (prefix.clone(), uri.clone()))
+        .collect()
+}
+
+/// Attribute name declaring a namespace with the given prefix
+pub(crate) fn declaration_name(prefix: &str) -> String {
+    if prefix == NS_NO_PREFIX {
+        NS_XMLNS_PREFIX.to_string()
+    } else {
+        format!("{}:{}", NS_XMLNS_PREFIX, prefix)
+    }
+}
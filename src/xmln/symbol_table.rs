@@ -1,16 +1,62 @@
 use std::{collections::HashMap, fmt::Display};
 
-use itertools::Itertools;
+use crate::{XFlateError, xmln::header::HeaderReader};
+
+/// Number of codes that can never be assigned to a symbol.
+/// The all-zero code is reserved, since a lone `0` denotes
+/// the closing of an element in XMLN.
+pub const RESERVED_SYMBOL_CODES: usize = 1;
+
+/// Largest code size for which the code space fits in a `usize`.
+pub const MAX_SYMBOL_CODE_SIZE: u8 = 19;
+
+/// Returns how many symbols can be encoded with codes of
+/// the given size, once the reserved codes are accounted for.
+pub fn symbol_capacity(code_size: u8) -> usize {
+    if code_size == 0 || code_size > MAX_SYMBOL_CODE_SIZE {
+        return 0;
+    }
+
+    usize::pow(10, code_size as u32) - RESERVED_SYMBOL_CODES
+}
+
+/// Returns the smallest code size able to encode `symbol_count`
+/// unique symbols, or `None` if no supported size is large enough.
+/// The returned size is always at least 1.
+pub fn min_symbol_code_size(symbol_count: usize) -> Option<u8> {
+    (1..=MAX_SYMBOL_CODE_SIZE).find(|&size| symbol_capacity(size) >= symbol_count)
+}
+
+/// Split a run of digits into codes of `code_size` digits.
+/// Fails unless the run is a whole number of codes.
+pub(crate) fn split_codes(
+    codes: &str,
+    code_size: u8,
+) -> Result<impl Iterator<Item = &str>, XFlateError> {
+    let size = code_size as usize;
+    if size == 0 || !codes.is_ascii() || !codes.len().is_multiple_of(size) {
+        return Err(XFlateError::XmlNError(format!(
+            "Symbol codes are not a whole number of {} digit codes: {}",
+            code_size, codes
+        )));
+    }
+
+    Ok((0..codes.len())
+        .step_by(size)
+        .map(move |i| &codes[i..i + size]))
+}
 
 pub trait XmlNSymbolTable {
     /// Creates a new symbol table with
     /// the specified code size
     fn new(code_size: u8) -> Self;
 
-    /// Returns number encoding of given symbol
+    /// Returns number encoding of given symbol.
+    /// Returns `None` if the symbol is new and the table is full.
     fn encode(&mut self, symbol: char) -> Option<&str>;
 
-    /// Returns the symbol for a given encoded string
+    /// Returns the symbol for a given encoded string,
+    /// or `None` if the code is unknown or of the wrong size
     fn decode(&self, code: &str) -> Option<char>;
 
     /// Returns the size of encodings
@@ -45,8 +91,77 @@ pub trait XmlNSymbolTable {
 
         header.bytes().collect()
     }
+
+    /// Parses a header written by `to_header`, returning the
+    /// table and the number of bytes the header takes up.
+    /// Anything following the header is left alone.
+    fn from_header(header: &[u8]) -> Result<(Self, usize), XFlateError>
+    where
+        Self: Sized,
+    {
+        let mut reader = HeaderReader::new(header, "symbol");
+        reader.expect(b'C')?;
+        reader.expect(b' ')?;
+
+        let code_size: u8 = reader.number("code size")?;
+        if code_size == 0 || code_size > MAX_SYMBOL_CODE_SIZE {
+            return Err(reader.error(format!(
+                "code size {} is not between 1 and {}",
+                code_size, MAX_SYMBOL_CODE_SIZE
+            )));
+        }
+
+        let symbol_count: usize = reader.number("symbol count")?;
+        if symbol_count > symbol_capacity(code_size) {
+            return Err(reader.error(format!(
+                "{} symbols exceed the {} codes of size {}",
+                symbol_count,
+                symbol_capacity(code_size),
+                code_size
+            )));
+        }
+        if symbol_count > reader.remaining().len() {
+            return Err(reader.error(format!(
+                "{} symbols can't fit in the remaining {} bytes",
+                symbol_count,
+                reader.remaining().len()
+            )));
+        }
+
+        // Symbols are whole characters, so they all
+        // lie within the valid UTF-8 that follows
+        let valid = reader
+            .remaining()
+            .utf8_chunks()
+            .next()
+            .map_or("", |chunk| chunk.valid());
+
+        let mut table = Self::new(code_size);
+        for symbol in valid.chars().take(symbol_count) {
+            let count = table.symbol_count();
+            table.encode(symbol);
+            if table.symbol_count() == count {
+                return Err(reader.error(format!("symbol {} appears twice", symbol.escape_debug())));
+            }
+
+            reader.advance(symbol.len_utf8());
+        }
+
+        if table.symbol_count() < symbol_count {
+            return Err(reader.error(format!(
+                "expected {} symbols, found {} followed by invalid UTF-8 or the end",
+                symbol_count,
+                table.symbol_count()
+            )));
+        }
+
+        reader.expect(b' ')?;
+
+        Ok((table, reader.position()))
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct XmlNDynamicSymbolTable {
     /// Maps symbols to encoded strings
     encoder: HashMap<char, String>,
@@ -59,9 +174,14 @@ pub struct XmlNDynamicSymbolTable {
 }
 
 impl XmlNDynamicSymbolTable {
+    /// Creates a table holding the given symbols, using the
+    /// smallest code size able to represent all of them.
+    ///
+    /// # Panics
+    /// If there are too many symbols for any supported code size.
     pub fn from_symbols(symbols: &[char]) -> Self {
-        // Round up to the nearest power of 10
-        let code_size = (symbols.len() as f64).log(10.0).ceil() as u8;
+        let code_size = min_symbol_code_size(symbols.len())
+            .unwrap_or_else(|| panic!("Too many symbols: {}", symbols.len()));
 
         let mut encoder = HashMap::new();
         let mut decoder = HashMap::new();
@@ -74,12 +194,12 @@ impl XmlNDynamicSymbolTable {
             decoder.insert(code_str, symbol);
         }
 
-        return XmlNDynamicSymbolTable {
+        XmlNDynamicSymbolTable {
             encoder,
             decoder,
             code_size,
             symbols: symbols.to_vec(),
-        };
+        }
     }
 
     fn get_next_code(&self) -> usize {
@@ -103,8 +223,8 @@ impl XmlNSymbolTable for XmlNDynamicSymbolTable {
         if !self.encoder.contains_key(&symbol) {
             let code = self.get_next_code();
 
-            if code >= usize::pow(10, self.code_size as u32) {
-                panic!("Symbol table overflow: cannot encode {}", symbol);
+            if code > symbol_capacity(self.code_size) {
+                return None;
             }
 
             let code_str = to_code_str(code, self.code_size);
@@ -118,11 +238,7 @@ impl XmlNSymbolTable for XmlNDynamicSymbolTable {
 
     fn decode(&self, code: &str) -> Option<char> {
         if code.len() != self.code_size as usize {
-            panic!(
-                "Invalid code length: expected size {}, got {}",
-                self.code_size,
-                code.len()
-            );
+            return None;
         }
 
         self.decoder.get(code).copied()
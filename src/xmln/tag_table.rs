@@ -1,6 +1,9 @@
 use std::{collections::HashMap, fmt::Display};
 
-use itertools::Itertools;
+use crate::{XFlateError, xmln::header::HeaderReader};
+
+/// Most tags and attribute names a table can hold, one per `u16` code
+pub const MAX_TAG_COUNT: usize = u16::MAX as usize + 1;
 
 /// Tag table is used to encode and decode XML tags and
 /// attributes to/from a numeric code.
@@ -12,7 +15,8 @@ use itertools::Itertools;
 /// Currently, codes are assigned using u16, meaning
 /// it can hold up to 65536 unique tags and attributes.
 pub trait XmlNTagTable {
-    /// Encode a tag a tag
+    /// Encode a tag a tag.
+    /// Returns `None` if the tag is new and the table is full.
     fn encode(&mut self, tag: &str) -> Option<u16>;
 
     /// Decode a tag
@@ -32,6 +36,8 @@ pub trait XmlNTagTable {
     /// <tags>      := <tag> <tags>|<tag>
     /// <tag>       := [str]
     /// E <tag_count> <tag...>
+    ///
+    /// Spaces and backslashes within a tag are escaped with a backslash.
     fn to_header(&self) -> Vec<u8> {
         let mut header = String::new();
         header.push('E');
@@ -40,14 +46,92 @@ pub trait XmlNTagTable {
         header.push(' ');
 
         for tag in self.iter_tags() {
-            header.push_str(tag);
+            for c in tag.chars() {
+                if c == ' ' || c == '\\' {
+                    header.push('\\');
+                }
+                header.push(c);
+            }
             header.push(' ');
         }
 
         header.bytes().collect()
     }
+
+    /// Parses a header written by `to_header`, returning the
+    /// table and the number of bytes the header takes up.
+    /// Anything following the header is left alone.
+    fn from_header(header: &[u8]) -> Result<(Self, usize), XFlateError>
+    where
+        Self: Sized + Default,
+    {
+        let mut reader = HeaderReader::new(header, "tag");
+        reader.expect(b'E')?;
+        reader.expect(b' ')?;
+
+        let tag_count: usize = reader.number("tag count")?;
+        if tag_count > MAX_TAG_COUNT {
+            return Err(reader.error(format!(
+                "{} tags exceed the maximum of {}",
+                tag_count, MAX_TAG_COUNT
+            )));
+        }
+        // Every tag takes up at least two bytes
+        if tag_count > reader.remaining().len() / 2 {
+            return Err(reader.error(format!(
+                "{} tags can't fit in the remaining {} bytes",
+                tag_count,
+                reader.remaining().len()
+            )));
+        }
+
+        let mut table = Self::default();
+        for index in 0..tag_count {
+            let tag = read_tag(&mut reader, index)?;
+
+            let code = table.tag_count();
+            if table.encode(&tag) != Some(code as u16) || table.tag_count() == code {
+                return Err(reader.error(format!("tag {} appears twice", tag)));
+            }
+        }
+
+        Ok((table, reader.position()))
+    }
 }
 
+/// Read an escaped tag and the space ending it
+fn read_tag(reader: &mut HeaderReader, index: usize) -> Result<String, XFlateError> {
+    let mut tag = Vec::new();
+    let mut escaped = false;
+    let mut end = None;
+
+    for (i, &byte) in reader.remaining().iter().enumerate() {
+        match (escaped, byte) {
+            (false, b'\\') => escaped = true,
+            (false, b' ') => {
+                end = Some(i);
+                break;
+            }
+            _ => {
+                tag.push(byte);
+                escaped = false;
+            }
+        }
+    }
+
+    let end = end.ok_or_else(|| reader.error(format!("tag {} is not terminated", index)))?;
+    if tag.is_empty() {
+        return Err(reader.error(format!("tag {} is empty", index)));
+    }
+    let tag = String::from_utf8(tag)
+        .map_err(|_| reader.error(format!("tag {} is not valid UTF-8", index)))?;
+
+    reader.advance(end + 1);
+
+    Ok(tag)
+}
+
+#[derive(Debug, Clone)]
 pub struct XmlNDynamicTagTable {
     encoder: HashMap<String, u16>,
     decoder: HashMap<u16, String>,
@@ -64,9 +148,19 @@ impl XmlNDynamicTagTable {
     }
 }
 
+impl Default for XmlNDynamicTagTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl XmlNTagTable for XmlNDynamicTagTable {
     fn encode(&mut self, tag: &str) -> Option<u16> {
         if !self.encoder.contains_key(tag) {
+            if self.encoder.len() >= MAX_TAG_COUNT {
+                return None;
+            }
+
             let code = self.encoder.len() as u16;
             self.encoder.insert(tag.to_string(), code);
             self.decoder.insert(code, tag.to_string());
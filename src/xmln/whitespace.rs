@@ -0,0 +1,94 @@
+use xml::attribute::OwnedAttribute;
+
+use crate::XFlateError;
+
+/// Line break and indentation unit of a pretty-printed document.
+///
+/// Whitespace that is exactly a line break followed by the unit once
+/// per level of depth is encoded as a single `I` token. The first `I`
+/// is followed by the symbols of the line break and a single unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Indentation {
+    line_break: &'static str,
+    unit: String,
+}
+
+impl Indentation {
+    /// Infer the indentation from whitespace found at `depth`.
+    /// Fails unless the whitespace is a line break followed by
+    /// `depth` repetitions of a run of spaces or tabs.
+    pub fn detect(whitespace: &str, depth: usize) -> Option<Self> {
+        let (line_break, rest) = split_line_break(whitespace)?;
+
+        // Only spaces and tabs, so the unit ends on a char boundary
+        if depth == 0 || !rest.chars().all(|c| c == ' ' || c == '\t') {
+            return None;
+        }
+        if rest.len() % depth != 0 {
+            return None;
+        }
+
+        let unit = &rest[..rest.len() / depth];
+        if unit.repeat(depth) != rest {
+            return None;
+        }
+
+        Some(Indentation {
+            line_break,
+            unit: unit.to_string(),
+        })
+    }
+
+    /// Parse the declaration following the first `I` token
+    pub fn from_declaration(declaration: &str) -> Result<Self, XFlateError> {
+        split_line_break(declaration)
+            .filter(|(_, unit)| unit.chars().all(|c| c == ' ' || c == '\t'))
+            .map(|(line_break, unit)| Indentation {
+                line_break,
+                unit: unit.to_string(),
+            })
+            .ok_or_else(|| {
+                XFlateError::XmlNError(format!("Invalid indentation: {:?}", declaration))
+            })
+    }
+
+    /// Text of the declaration following the first `I` token
+    pub fn declaration(&self) -> String {
+        self.line_break.to_string() + &self.unit
+    }
+
+    /// Whitespace for a line at the given depth
+    pub fn at(&self, depth: usize) -> String {
+        self.line_break.to_string() + &self.unit.repeat(depth)
+    }
+
+    /// Length of `at(depth)`, known before allocating it
+    pub fn len_at(&self, depth: usize) -> usize {
+        self.unit
+            .len()
+            .saturating_mul(depth)
+            .saturating_add(self.line_break.len())
+    }
+}
+
+fn split_line_break(whitespace: &str) -> Option<(&'static str, &str)> {
+    ["\r\n", "\n"].into_iter().find_map(|line_break| {
+        whitespace
+            .strip_prefix(line_break)
+            .map(|rest| (line_break, rest))
+    })
+}
+
+/// Whether whitespace must be kept in an element with the given
+/// attributes, following `xml:space` or else its parent element
+pub(crate) fn preserves_space(attributes: &[OwnedAttribute], parent: bool) -> bool {
+    let space = attributes
+        .iter()
+        .find(|attr| attr.name.prefix.as_deref() == Some("xml") && attr.name.local_name == "space");
+
+    match space.map(|attr| attr.value.as_str()) {
+        Some("preserve") => true,
+        Some("default") => false,
+        _ => parent,
+    }
+}
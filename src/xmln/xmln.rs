@@ -4,16 +4,50 @@ use std::io::BufReader;
 use std::io::Read;
 use std::str::Chars;
 use xml::ParserConfig;
-use xml::reader::XmlEvent;
+use xml::common::{Position, TextPosition};
+use xml::namespace::Namespace;
+use xml::reader::{EventReader, XmlEvent};
 
 use crate::XFlateError;
+use crate::XFlateLimits;
 use crate::XmlNSymbolTable;
 use crate::XmlNTagTable;
 use crate::consume_until_space;
+use crate::split_codes;
 use crate::util::consume_until_whitespace;
+use crate::xmln::doctype::{ENTITY_REFERENCE, XmlNEntities, parser_config};
+use crate::xmln::formatting::{ByteOffsets, XmlNFormatting, push_piece, restore};
+use crate::xmln::namespace::{declaration_name, declared_namespaces, qualified_name};
+use crate::xmln::whitespace::{Indentation, preserves_space};
 
 pub type XmlN = String;
 
+/// Size of the blocks `decode_xmln_streamed` passes on
+const BLOCK_SIZE: usize = 16 * 1024;
+
+/// Options for `encode_xmln_with` and `encode_xmln_lossless`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XmlNOptions {
+    /// How references to entities declared in the document type are encoded
+    pub entities: XmlNEntities,
+    /// Encode whitespace that follows the document's indentation
+    /// with a single token. The output is the same either way.
+    pub indentation: bool,
+    /// Leave out text made only of whitespace, except in elements
+    /// with `xml:space="preserve"`. This changes the document.
+    pub strip_whitespace: bool,
+}
+
+impl Default for XmlNOptions {
+    fn default() -> Self {
+        XmlNOptions {
+            entities: XmlNEntities::Expand,
+            indentation: true,
+            strip_whitespace: false,
+        }
+    }
+}
+
 pub fn encode_xmln<D, S, T>(
     data: D,
     sym_table: &mut S,
@@ -28,10 +62,241 @@ where
     let config = ParserConfig::new();
     let parser = config.create_reader(buf);
 
+    encode_events(
+        parsed_events(parser),
+        sym_table,
+        tag_table,
+        &XmlNOptions::default(),
+        None,
+    )
+}
+
+/// Encode XMLN with the given options. Unlike `encode_xmln`,
+/// this reads the whole document before encoding it.
+pub fn encode_xmln_with<D, S, T>(
+    mut data: D,
+    sym_table: &mut S,
+    tag_table: &mut T,
+    options: &XmlNOptions,
+) -> Result<XmlN, XFlateError>
+where
+    D: Read,
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    let mut xml = Vec::new();
+    data.read_to_end(&mut xml)
+        .map_err(|e| XFlateError::XmlNError(format!("Failed to read XML: {}", e)))?;
+
+    let config = parser_config(&xml, options.entities)?;
+    let parser = config.create_reader(xml.as_slice());
+
+    encode_events(parsed_events(parser), sym_table, tag_table, options, None)
+}
+
+/// Encode XMLN from events, such as those generated by a program,
+/// without going through the XML text. Namespaces in scope are taken
+/// from each start element, as the parser reports them.
+///
+/// Entity references are written as the text holds them, so
+/// `options.entities` has no effect. Text holding U+FFFF returns an
+/// error: it isn't allowed in XML, and XMLN uses it to mark entity
+/// references.
+pub fn encode_xmln_events<I, S, T>(
+    events: I,
+    sym_table: &mut S,
+    tag_table: &mut T,
+    options: &XmlNOptions,
+) -> Result<XmlN, XFlateError>
+where
+    I: IntoIterator<Item = XmlEvent>,
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    let events = events
+        .into_iter()
+        .map(|event| (check_event_text(event), TextPosition::new()));
+
+    encode_events(events, sym_table, tag_table, options, None)
+}
+
+/// Reject text holding the character that marks entity references,
+/// as it would decode as a reference
+fn check_event_text(event: XmlEvent) -> Result<XmlEvent, XFlateError> {
+    let marked = match &event {
+        XmlEvent::Characters(text) | XmlEvent::Whitespace(text) | XmlEvent::CData(text) => {
+            text.contains(ENTITY_REFERENCE)
+        }
+        XmlEvent::StartElement { attributes, .. } => attributes
+            .iter()
+            .any(|attr| attr.value.contains(ENTITY_REFERENCE)),
+        _ => false,
+    };
+
+    if marked {
+        return Err(XFlateError::XmlNError(format!(
+            "Text can't hold {:?}, which isn't allowed in XML",
+            ENTITY_REFERENCE
+        )));
+    }
+
+    Ok(event)
+}
+
+/// Encode XMLN along with the formatting needed to restore
+/// the original bytes. See `decode_xmln_lossless`.
+///
+/// Anything XMLN leaves out, such as the XML declaration, comments,
+/// CDATA sections, entity references and the quotes around attribute
+/// values, is kept in the formatting. The document must be UTF-8.
+pub fn encode_xmln_lossless<S, T>(
+    xml: &[u8],
+    sym_table: &mut S,
+    tag_table: &mut T,
+    options: &XmlNOptions,
+) -> Result<(XmlN, XmlNFormatting), XFlateError>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    let text = std::str::from_utf8(xml)
+        .map_err(|e| XFlateError::XmlNError(format!("Lossless encoding requires UTF-8: {}", e)))?;
+
+    let config = parser_config(xml, options.entities)?;
+    let parser = config.create_reader(xml);
+
+    let mut positions = Vec::new();
+    let xmln = encode_events(
+        parsed_events(parser),
+        sym_table,
+        tag_table,
+        options,
+        Some(&mut positions),
+    )?;
+
+    // Where each token starts in the original, and in the decoded output.
+    // Starts never decrease, so the pieces always add up to the original.
+    let mut offsets = ByteOffsets::new(text);
+    let mut last = 0;
+    let starts = positions
+        .into_iter()
+        .map(|(pos, markup)| {
+            let offset = offsets.offset(pos);
+
+            // The parser places a root element with nothing before it,
+            // and text starting with a reference, at their ends. Markup
+            // starts at a '<', and text right after the markup before it.
+            let offset = match (markup, text[offset..].starts_with('<')) {
+                (true, false) => text[..offset].rfind('<').unwrap_or(offset),
+                (false, true) => text[..offset].rfind('>').map_or(offset, |i| i + 1),
+                _ => offset,
+            };
+
+            last = offset.max(last);
+            last
+        })
+        .collect::<Vec<_>>();
+
+    let mut boundaries = Vec::new();
+    let decoded = decode_xmln_inner(
+        &xmln,
+        sym_table,
+        tag_table,
+        &XFlateLimits::unlimited(),
+        Some(&mut boundaries),
+        None,
+    )?;
+
+    if boundaries.len() != starts.len() {
+        return Err(XFlateError::XmlNError(format!(
+            "Encoded {} tokens, but decoded {}",
+            starts.len(),
+            boundaries.len()
+        )));
+    }
+
+    let mut formatting = XmlNFormatting::new();
+    let mut skipped = 0;
+    for (original, decoded) in pieces(text, &starts).zip(pieces(&decoded, &boundaries)) {
+        push_piece(&mut formatting, &mut skipped, original, decoded);
+    }
+
+    Ok((xmln, formatting))
+}
+
+/// Events read by the parser, each with the position it starts at
+fn parsed_events<R>(
+    mut parser: EventReader<R>,
+) -> impl Iterator<Item = (Result<XmlEvent, XFlateError>, TextPosition)>
+where
+    R: Read,
+{
+    std::iter::from_fn(move || {
+        let e = parser
+            .next()
+            .map_err(|e| XFlateError::XmlNError(format!("XML parsing error: {}", e)));
+
+        Some((e, parser.position()))
+    })
+}
+
+/// Translate events to XMLN, up to the end of the document.
+///
+/// With `positions`, the start of each event producing a token is
+/// recorded, along with whether it's markup rather than text. Events
+/// XMLN can't represent are then skipped, as the formatting keeps them.
+fn encode_events<I, S, T>(
+    events: I,
+    sym_table: &mut S,
+    tag_table: &mut T,
+    options: &XmlNOptions,
+    mut positions: Option<&mut Vec<(TextPosition, bool)>>,
+) -> Result<XmlN, XFlateError>
+where
+    I: IntoIterator<Item = (Result<XmlEvent, XFlateError>, TextPosition)>,
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
     let mut xmln = XmlN::new();
     let mut translate = String::new();
+    // Whether the last element started has no content so far
+    let mut empty = false;
+    // Whether each open element preserves whitespace
+    let mut preserve: Vec<bool> = Vec::new();
+    // Namespaces in scope in each open element
+    let mut namespaces: Vec<Namespace> = Vec::new();
+    let no_namespaces = Namespace::empty();
+    // Whitespace is held back until the next token, since the depth
+    // of the indentation it is compared with depends on that token
+    let mut pending: Option<(String, TextPosition)> = None;
+    let mut indentation: Option<Indentation> = None;
+
+    for (e, position) in events {
+        let starts_element = matches!(e, Ok(XmlEvent::StartElement { .. }));
+        let markup = starts_element || matches!(e, Ok(XmlEvent::EndElement { .. }));
+        let strip = options.strip_whitespace && preserve.last() != Some(&true);
+
+        let makes_token = match &e {
+            Ok(XmlEvent::StartElement { .. }) | Ok(XmlEvent::EndElement { .. }) => true,
+            Ok(XmlEvent::Characters(data)) => !data.is_empty(),
+            Ok(XmlEvent::Whitespace(data)) => !data.is_empty() && !strip,
+            _ => false,
+        };
+
+        if let Some((whitespace, whitespace_position)) = pending.take_if(|_| makes_token) {
+            // A closing token ends the element the whitespace is in
+            let depth = match &e {
+                Ok(XmlEvent::EndElement { .. }) => preserve.len().saturating_sub(1),
+                _ => preserve.len(),
+            };
+
+            put_whitespace(&mut xmln, whitespace, depth, &mut indentation, sym_table)?;
+            if let Some(positions) = positions.as_deref_mut() {
+                positions.push((whitespace_position, false));
+            }
+            empty = false;
+        }
 
-    for e in parser {
         match e {
             Ok(XmlEvent::StartDocument {
                 version: _,
@@ -44,59 +309,102 @@ where
                 ); */
             }
 
-            Ok(XmlEvent::EndDocument) => { /* println!("End Document"); */ }
+            Ok(XmlEvent::EndDocument) => break,
 
             Ok(XmlEvent::StartElement {
                 name: tag,
                 attributes,
-                namespace: _,
+                namespace,
             }) => {
-                put_elem_start_tag(&mut translate, tag.to_string().as_str(), tag_table)?;
+                preserve.push(preserves_space(
+                    &attributes,
+                    preserve.last().copied().unwrap_or(false),
+                ));
+                put_elem_start_tag(&mut translate, &qualified_name(&tag), tag_table)?;
+
+                // Declarations are written as attributes, before the others
+                let parent = namespaces.last().unwrap_or(&no_namespaces);
+                for (prefix, uri) in declared_namespaces(&namespace, parent) {
+                    put_attr_tag(&mut translate, &declaration_name(&prefix), tag_table)?;
+                    put_symbols(&mut translate, uri, sym_table)?;
+                }
+                namespaces.push(namespace);
 
                 for attr in attributes {
-                    let attr_name = match attr.name.prefix {
-                        Some(prefix) => prefix + ":" + &attr.name.local_name,
-                        None => attr.name.local_name.clone(),
-                    };
-
-                    put_attr_tag(&mut translate, attr_name.as_str(), tag_table)?;
+                    put_attr_tag(&mut translate, &qualified_name(&attr.name), tag_table)?;
                     put_symbols(&mut translate, attr.value, sym_table)?;
                 }
             }
 
+            // An element without content is closed by a single token.
+            // It adds to the start tag rather than starting a new piece.
+            Ok(XmlEvent::EndElement { name: _ }) if empty => {
+                preserve.pop();
+                namespaces.pop();
+                xmln.push_str(" S");
+                empty = false;
+                continue;
+            }
+
             Ok(XmlEvent::EndElement { name: _ }) => {
+                preserve.pop();
+                namespaces.pop();
                 translate.push(' ');
                 translate.push('0');
             }
 
-            Ok(XmlEvent::Characters(data)) => {
-                put_symbols(&mut translate, data, sym_table)?;
+            // An empty text would leave nothing between its separators
+            Ok(XmlEvent::Characters(data)) | Ok(XmlEvent::Whitespace(data)) if data.is_empty() => {
+                continue;
+            }
+
+            Ok(XmlEvent::Whitespace(_)) if strip => continue,
+
+            Ok(XmlEvent::Whitespace(data)) if options.indentation => {
+                pending = Some((data, position));
+                continue;
             }
 
-            Ok(XmlEvent::Whitespace(data)) => {
+            Ok(XmlEvent::Characters(data)) | Ok(XmlEvent::Whitespace(data)) => {
                 put_symbols(&mut translate, data, sym_table)?;
             }
 
-            Ok(XmlEvent::CData(data)) => {
-                panic!("Not supported CData: {}", data);
+            Ok(XmlEvent::CData(_)) | Ok(XmlEvent::ProcessingInstruction { .. })
+                if positions.is_some() =>
+            {
+                continue;
             }
 
-            Ok(XmlEvent::ProcessingInstruction { name, data }) => {
-                panic!(
-                    "Not supported Processing Instruction: name={}, data={:?}",
-                    name, data
-                );
+            Ok(XmlEvent::CData(_)) => {
+                return Err(XFlateError::XmlNError(
+                    "CData not implemented yet".to_string(),
+                ));
             }
 
-            Ok(XmlEvent::Comment(data)) => {
-                panic!("Not supported Comment: {}", data);
+            Ok(XmlEvent::ProcessingInstruction { name, .. }) => {
+                return Err(XFlateError::XmlNError(format!(
+                    "Processing instruction not implemented yet: {}",
+                    name
+                )));
             }
 
-            Err(e) => {
-                return Err(XFlateError::XmlNError(format!("XML parsing error: {}", e)));
+            Ok(XmlEvent::Comment(_)) => {
+                return Err(XFlateError::XmlNError(
+                    "Comment not implemented yet".to_string(),
+                ));
             }
+
+            Err(e) => return Err(e),
         }
 
+        if let Some(positions) = positions.as_deref_mut()
+            && !translate.is_empty()
+        {
+            positions.push((position, markup));
+        }
+
+        empty = starts_element;
+
         xmln.push_str(&translate);
         translate.clear();
     }
@@ -107,6 +415,56 @@ where
     Ok(xmln)
 }
 
+/// Split `text` at the given offsets, starting with the part before the first
+fn pieces<'a>(text: &'a str, offsets: &'a [usize]) -> impl Iterator<Item = &'a str> {
+    let starts = std::iter::once(0).chain(offsets.iter().copied());
+    let ends = offsets.iter().copied().chain(std::iter::once(text.len()));
+
+    starts.zip(ends).map(|(start, end)| &text[start..end])
+}
+
+/// Put whitespace found at `depth`, as an indentation token
+/// if it matches the indentation of the document
+fn put_whitespace<S>(
+    xmln: &mut XmlN,
+    whitespace: String,
+    depth: usize,
+    indentation: &mut Option<Indentation>,
+    sym_table: &mut S,
+) -> Result<(), XFlateError>
+where
+    S: XmlNSymbolTable,
+{
+    match indentation {
+        Some(indentation) if indentation.at(depth) == whitespace => {
+            xmln.push_str(" I");
+        }
+        Some(_) => put_symbols(xmln, whitespace, sym_table)?,
+        None => match Indentation::detect(&whitespace, depth) {
+            // The first indentation token declares the indentation
+            Some(detected) => {
+                xmln.push_str(" I");
+                for c in detected.declaration().chars() {
+                    xmln.push_str(encode_symbol(c, sym_table)?);
+                }
+                *indentation = Some(detected);
+            }
+            None => put_symbols(xmln, whitespace, sym_table)?,
+        },
+    }
+
+    Ok(())
+}
+
+fn encode_symbol<S>(c: char, sym_table: &mut S) -> Result<&str, XFlateError>
+where
+    S: XmlNSymbolTable,
+{
+    sym_table
+        .encode(c)
+        .ok_or_else(|| XFlateError::XmlNError(format!("Failed to encode symbol: {}", c)))
+}
+
 fn put_symbols<S>(
     translate: &mut String,
     token: String,
@@ -118,10 +476,7 @@ where
     translate.push(' ');
 
     for c in token.chars() {
-        let enc = sym_table
-            .encode(c)
-            .ok_or_else(|| XFlateError::XmlNError(format!("Failed to encode symbol: {}", c)))?;
-        translate.push_str(enc);
+        translate.push_str(encode_symbol(c, sym_table)?);
     }
     Ok(())
 }
@@ -177,15 +532,106 @@ pub fn decode_xmln<S, T>(
     sym_table: &mut S,
     tag_table: &mut T,
 ) -> Result<String, XFlateError>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    decode_xmln_limited(xmln, sym_table, tag_table, &XFlateLimits::unlimited())
+}
+
+/// Decode XMLN, failing as soon as the output or the
+/// element depth goes past the given limits.
+pub fn decode_xmln_limited<S, T>(
+    xmln: &str,
+    sym_table: &mut S,
+    tag_table: &mut T,
+    limits: &XFlateLimits,
+) -> Result<String, XFlateError>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    decode_xmln_inner(xmln, sym_table, tag_table, limits, None, None)
+}
+
+/// Decode XMLN like `decode_xmln_limited`, passing the output to
+/// `sink` a block at a time as it is decoded, rather than returning
+/// it whole. Output passed on before an error is left as it is.
+#[cfg(feature = "async")]
+pub(crate) fn decode_xmln_streamed<S, T, F>(
+    xmln: &str,
+    sym_table: &mut S,
+    tag_table: &mut T,
+    limits: &XFlateLimits,
+    mut sink: F,
+) -> Result<(), XFlateError>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+    F: FnMut(&str) -> Result<(), XFlateError>,
+{
+    decode_xmln_inner(xmln, sym_table, tag_table, limits, None, Some(&mut sink))?;
+    Ok(())
+}
+
+/// Decode XMLN produced by `encode_xmln_lossless`,
+/// restoring the original bytes from the formatting.
+pub fn decode_xmln_lossless<S, T>(
+    xmln: &str,
+    formatting: &[u8],
+    sym_table: &mut S,
+    tag_table: &mut T,
+    limits: &XFlateLimits,
+) -> Result<String, XFlateError>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    let mut boundaries = Vec::new();
+    let decoded = decode_xmln_inner(
+        xmln,
+        sym_table,
+        tag_table,
+        limits,
+        Some(&mut boundaries),
+        None,
+    )?;
+
+    let restored = restore(pieces(&decoded, &boundaries), formatting)?;
+    limits.check_output(restored.len())?;
+
+    Ok(restored)
+}
+
+/// Receives the blocks of `decode_xmln_streamed`
+type Sink<'a> = &'a mut dyn FnMut(&str) -> Result<(), XFlateError>;
+
+/// Decode XMLN. With `boundaries`, the output offset
+/// of every tag, text and closing token is recorded. With `sink`,
+/// the output is passed on in blocks, and nothing is returned.
+fn decode_xmln_inner<S, T>(
+    xmln: &str,
+    sym_table: &mut S,
+    tag_table: &mut T,
+    limits: &XFlateLimits,
+    mut boundaries: Option<&mut Vec<usize>>,
+    mut sink: Option<Sink<'_>>,
+) -> Result<String, XFlateError>
 where
     S: XmlNSymbolTable,
     T: XmlNTagTable,
 {
     let mut decoded = String::new();
+    // Bytes already passed to the sink
+    let mut written = 0;
     let mut chars = xmln.chars().multipeek();
 
     // Store opened tags to handle nested strctures
     let mut tag_stack = Vec::new();
+    // Whether the last start tag was left open for a self-closing token
+    let mut open = false;
+    // Declared by the first indentation token
+    let mut indentation: Option<Indentation> = None;
 
     while let Some(token) = chars.peek().copied() {
         let token_next = chars.peek().copied();
@@ -197,6 +643,7 @@ where
             // Opening tag
             ('T', Some('0'..='9')) => {
                 chars.next(); // Consume 'T'
+                mark(&mut boundaries, &decoded);
 
                 let code_str = consume_until_space(&mut chars);
                 if code_str.is_empty() {
@@ -211,22 +658,25 @@ where
                     XFlateError::XmlNError(format!("Unknown tag code: {}", tag_code))
                 })?;
 
-                decoded.push_str("<");
+                decoded.push('<');
                 decoded.push_str(tag);
 
                 tag_stack.push(tag.to_string());
+                limits.check_depth(tag_stack.len())?;
 
-                chars.peek(); // Whitespace
-                // Check if this element has attributes
-                if chars.peek().cloned() != Some('A') {
-                    decoded.push('>');
-                }
+                open = end_start_tag(&mut chars, &mut decoded);
             }
 
             // Attribute
             ('A', _) => {
                 chars.next(); // Consume 'A'
 
+                if !open {
+                    return Err(XFlateError::XmlNError(
+                        "Attribute must follow a start tag".to_string(),
+                    ));
+                }
+
                 let attr_code_str = consume_until_space(&mut chars);
                 if attr_code_str.is_empty() {
                     return Err(XFlateError::XmlNError("Empty attribute code".to_string()));
@@ -241,32 +691,32 @@ where
                 })?;
 
                 chars.peek(); // Whitespace
-                // Check if there exists an attribute value
-                let attr_val = match (chars.peek().cloned(), chars.peek().cloned()) {
-                    (Some('1'..='9'), Some('1'..='9')) => {
+                // Check if there exists an attribute value. An empty
+                // value leaves a second whitespace in its place.
+                let attr_val = match chars.peek().cloned() {
+                    Some('0'..='9') => {
                         chars.next(); // Consume whitespace
-                        decode_text(&mut chars, sym_table)?
+                        decode_text(&mut chars, sym_table, true)?
+                    }
+                    _ => {
+                        chars.next(); // Consume one of the two whitespaces
+                        "".to_string()
                     }
-                    _ => "".to_string(),
                 };
 
                 decoded.push(' ');
-                decoded.push_str(&attr_name);
+                decoded.push_str(attr_name);
                 decoded.push_str("=\"");
                 decoded.push_str(&attr_val);
                 decoded.push('\"');
 
-                chars.peek(); // Whitespace
-                // Check if this is the last
-                // attribute for this element
-                if chars.peek().cloned() != Some('A') {
-                    decoded.push('>');
-                }
+                open = end_start_tag(&mut chars, &mut decoded);
             }
 
             // Closing tag
             ('0', Some(' ') | None) => {
                 chars.next(); // Consume '0'
+                mark(&mut boundaries, &decoded);
 
                 if let Some(tag) = tag_stack.pop() {
                     decoded.push_str("</");
@@ -279,9 +729,58 @@ where
                 }
             }
 
-            // Text content
-            ('0'..='9', Some('1'..='9')) => {
-                let text = decode_text(&mut chars, sym_table)?;
+            // Closing tag of an element without content
+            ('S', Some(' ') | None) => {
+                chars.next(); // Consume 'S'
+
+                if !open || tag_stack.pop().is_none() {
+                    return Err(XFlateError::XmlNError(
+                        "Self-closing tag must follow a start tag".to_string(),
+                    ));
+                }
+
+                decoded.push_str("/>");
+                open = false;
+            }
+
+            // Whitespace following the indentation of the document
+            ('I', _) => {
+                chars.next(); // Consume 'I'
+                mark(&mut boundaries, &decoded);
+
+                chars.reset_peek();
+                if let Some('0'..='9') = chars.peek() {
+                    let declaration = decode_text(&mut chars, sym_table, false)?;
+                    indentation = Some(Indentation::from_declaration(&declaration)?);
+                }
+
+                let indentation = indentation.as_ref().ok_or_else(|| {
+                    XFlateError::XmlNError("Indentation used before it is declared".to_string())
+                })?;
+
+                // A closing token ends the element the whitespace is in
+                chars.reset_peek();
+                chars.peek(); // Whitespace
+                let closing =
+                    chars.peek() == Some(&'0') && matches!(chars.peek(), Some(' ') | None);
+                let depth = if closing {
+                    tag_stack.len().saturating_sub(1)
+                } else {
+                    tag_stack.len()
+                };
+
+                // Deep indentation expands a lot, so check before allocating
+                limits.check_output(
+                    (written + decoded.len()).saturating_add(indentation.len_at(depth)),
+                )?;
+                decoded.push_str(&indentation.at(depth));
+            }
+
+            // Text content. Any digit run but a lone '0' is text,
+            // since the all-zero symbol code is never assigned.
+            ('0'..='9', _) => {
+                mark(&mut boundaries, &decoded);
+                let text = decode_text(&mut chars, sym_table, false)?;
                 decoded.push_str(&text);
             }
 
@@ -294,27 +793,82 @@ where
             }
         }
 
+        limits.check_output(written + decoded.len())?;
         chars.reset_peek();
+
+        if let Some(sink) = sink.as_mut()
+            && decoded.len() >= BLOCK_SIZE
+        {
+            sink(&decoded)?;
+            written += decoded.len();
+            decoded.clear();
+        }
+    }
+
+    if let Some(tag) = tag_stack.last() {
+        return Err(XFlateError::XmlNError(format!(
+            "Element {} is never closed",
+            tag
+        )));
+    }
+
+    if let Some(sink) = sink
+        && !decoded.is_empty()
+    {
+        sink(&decoded)?;
+        decoded.clear();
     }
 
     Ok(decoded)
 }
 
-fn decode_text<S>(chars: &mut MultiPeek<Chars<'_>>, sym_table: &S) -> Result<String, XFlateError>
+/// Close the start tag unless attributes follow, or the element
+/// has no content and is closed by the next token.
+/// Returns whether the start tag was left open.
+fn end_start_tag(chars: &mut MultiPeek<Chars<'_>>, decoded: &mut String) -> bool {
+    chars.reset_peek();
+    chars.peek(); // Whitespace
+
+    if matches!(chars.peek(), Some('A' | 'S')) {
+        return true;
+    }
+
+    decoded.push('>');
+    false
+}
+
+/// Record where the next token starts in the output
+fn mark(boundaries: &mut Option<&mut Vec<usize>>, decoded: &str) {
+    if let Some(boundaries) = boundaries {
+        boundaries.push(decoded.len());
+    }
+}
+
+/// Decode a run of symbol codes, escaping what can't appear
+/// literally in text, or in an attribute value if `attribute`
+fn decode_text<S>(
+    chars: &mut MultiPeek<Chars<'_>>,
+    sym_table: &S,
+    attribute: bool,
+) -> Result<String, XFlateError>
 where
     S: XmlNSymbolTable,
 {
     chars.reset_peek();
-    let code_strings = consume_until_whitespace(chars)
-        .chars()
-        .collect::<Vec<char>>()
-        .chunks(sym_table.code_size() as usize)
-        .map(|chunk| chunk.iter().collect::<String>())
-        .collect::<Vec<String>>();
+    let codes = consume_until_whitespace(chars);
 
     let mut result = String::new();
-    for code_str in code_strings {
-        match sym_table.decode(&code_str) {
+    for code_str in split_codes(&codes, sym_table.code_size())? {
+        match sym_table.decode(code_str) {
+            Some(ENTITY_REFERENCE) => result.push('&'),
+            Some('&') => result.push_str("&amp;"),
+            Some('<') => result.push_str("&lt;"),
+            Some('>') if !attribute => result.push_str("&gt;"),
+            Some('"') if attribute => result.push_str("&quot;"),
+            // Parsers would turn these into spaces
+            Some('\t') if attribute => result.push_str("&#9;"),
+            Some('\n') if attribute => result.push_str("&#10;"),
+            Some('\r') if attribute => result.push_str("&#13;"),
             Some(symbol) => result.push(symbol),
             None => {
                 return Err(XFlateError::XmlNError(format!(
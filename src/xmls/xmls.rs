@@ -5,7 +5,7 @@ pub type XmlS = Vec<u8>;
 /// Encode a string into XMLS format.
 ///
 /// This function packs a sequence of XMLN symbols into a byte array.
-/// XMLN is composed of 13 symbols, meaning we can represent each symbol with a 4-bit number.
+/// XMLN is composed of 15 symbols, meaning we can represent each symbol with a 4-bit number.
 /// Every pair of symbol (using its 4-bit code) is then packed into a single byte.
 ///
 /// # Arguments
@@ -15,7 +15,7 @@ pub type XmlS = Vec<u8>;
 /// * `Err(XmlsError)` - An error if the input contains invalid symbols
 pub fn encode_xmls(xmln: &str) -> Result<XmlS, XFlateError> {
     let mut chars = xmln.chars();
-    let mut encoding = XmlS::with_capacity((xmln.len() + 1) / 2);
+    let mut encoding = XmlS::with_capacity(xmln.len().div_ceil(2));
 
     while let Some(left) = chars.next() {
         let lft_nibble = encode_nibble(left)?;
@@ -40,9 +40,11 @@ pub fn decode_xmls(xmls: &[u8]) -> Result<XmlN, XFlateError> {
     for &byte in xmls {
         let (lft_nibble, rgt_nibble) = split(byte);
 
-        // There is one byte at the beginning that can be invalid
+        decoded.push(decode_nibble(lft_nibble)?);
+
+        // A zero nibble is only ever padding, added
+        // when the symbol count is uneven
         if rgt_nibble != 0 {
-            decoded.push(decode_nibble(lft_nibble)?);
             decoded.push(decode_nibble(rgt_nibble)?);
         }
     }
@@ -65,6 +67,8 @@ fn encode_nibble(symbol: char) -> Result<u8, XFlateError> {
         '7' => Ok(0xB),
         '8' => Ok(0xC),
         '9' => Ok(0xD),
+        'S' => Ok(0xE),
+        'I' => Ok(0xF),
         _ => Err(XFlateError::XmlSError(format!(
             "Unable to encode invalid symbol: {}",
             symbol
@@ -87,6 +91,8 @@ fn decode_nibble(nibble: u8) -> Result<char, XFlateError> {
         0xB => Ok('7'),
         0xC => Ok('8'),
         0xD => Ok('9'),
+        0xE => Ok('S'),
+        0xF => Ok('I'),
         _ => Err(XFlateError::XmlSError(format!(
             "Unable to decode invalid nibble: {}",
             nibble
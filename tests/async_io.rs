@@ -0,0 +1,163 @@
+#![cfg(feature = "async")]
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use xflate::{XFlateAsyncReader, XFlateAsyncWriter, XFlateConfig, XFlateError, XFlateLimits};
+
+mod common;
+
+use common::{XML, decompress, encodings};
+
+/// Limits the output and headers to `bytes`
+fn limited(bytes: usize) -> XFlateConfig {
+    common::limited(XFlateLimits {
+        max_output_bytes: bytes,
+        max_header_bytes: bytes,
+        ..XFlateLimits::default()
+    })
+}
+
+/// XFlate error wrapped in an I/O error
+fn xflate_error(err: io::Error) -> XFlateError {
+    *err.into_inner().unwrap().downcast().unwrap()
+}
+
+async fn compress(xml: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    XFlateAsyncReader::compress(xml.as_bytes(), XFlateConfig::default())
+        .read_to_end(&mut out)
+        .await
+        .unwrap();
+    out
+}
+
+#[tokio::test]
+async fn reader_round_trip() {
+    let compressed = compress(XML).await;
+
+    let mut out = String::new();
+    XFlateAsyncReader::decompress(compressed.as_slice(), XFlateConfig::default())
+        .read_to_string(&mut out)
+        .await
+        .unwrap();
+
+    assert_eq!(out, XML.trim_end());
+}
+
+#[tokio::test]
+async fn writer_round_trip() {
+    let mut writer = XFlateAsyncWriter::compress(Vec::new(), XFlateConfig::default());
+    writer.write_all(XML.as_bytes()).await.unwrap();
+    writer.shutdown().await.unwrap();
+    let compressed = writer.into_inner();
+
+    let mut writer = XFlateAsyncWriter::decompress(Vec::new(), XFlateConfig::default());
+    writer.write_all(&compressed).await.unwrap();
+    writer.shutdown().await.unwrap();
+
+    assert_eq!(writer.into_inner(), XML.trim_end().as_bytes());
+}
+
+#[tokio::test]
+async fn reader_rejects_input_over_the_limit() {
+    let mut out = Vec::new();
+    let err = XFlateAsyncReader::compress(XML.as_bytes(), limited(16))
+        .read_to_end(&mut out)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(xflate_error(err), XFlateError::LimitExceeded(_)));
+}
+
+#[tokio::test]
+async fn writer_rejects_input_over_the_limit() {
+    let mut writer = XFlateAsyncWriter::decompress(Vec::new(), limited(16));
+    let err = writer.write_all(&[0; 64 * 1024]).await.unwrap_err();
+
+    assert!(matches!(xflate_error(err), XFlateError::LimitExceeded(_)));
+}
+
+/// Document several blocks of output long
+fn large_document() -> String {
+    let items = (0..5000)
+        .map(|i| format!("\n  <item n=\"{}\">text &amp; more</item>", i))
+        .collect::<String>();
+    format!("<list>{}\n</list>", items)
+}
+
+async fn read_all<R>(mut reader: R) -> (Vec<u8>, io::Result<usize>)
+where
+    R: AsyncRead + Unpin,
+{
+    let mut out = Vec::new();
+    let result = reader.read_to_end(&mut out).await;
+    (out, result)
+}
+
+#[tokio::test]
+async fn large_documents_match_the_blocking_pipeline() {
+    let xml = large_document();
+
+    for config in encodings() {
+        for checksum in [false, true] {
+            let config = XFlateConfig {
+                checksum,
+                ..config.clone()
+            };
+            let compressed = common::compress(&config, &xml);
+
+            let (out, result) =
+                read_all(XFlateAsyncReader::compress(xml.as_bytes(), config.clone())).await;
+            result.unwrap();
+            assert_eq!(out, compressed);
+
+            let (out, result) = read_all(XFlateAsyncReader::decompress(
+                compressed.as_slice(),
+                config.clone(),
+            ))
+            .await;
+            result.unwrap();
+            assert_eq!(out, decompress(&config, &compressed).unwrap().as_bytes());
+        }
+    }
+}
+
+#[tokio::test]
+async fn output_is_returned_as_it_is_decoded() {
+    let xml = large_document();
+    let compressed = common::compress(&XFlateConfig::default(), &xml);
+
+    // Blocks decoded before the document goes past the limit
+    // are returned ahead of the error
+    let config = limited(xml.len() / 2);
+    let (out, result) =
+        read_all(XFlateAsyncReader::decompress(compressed.as_slice(), config)).await;
+
+    assert!(matches!(
+        xflate_error(result.unwrap_err()),
+        XFlateError::LimitExceeded(_)
+    ));
+    assert!(!out.is_empty());
+    assert!(xml.as_bytes().starts_with(&out));
+}
+
+#[tokio::test]
+async fn checksum_is_checked_before_any_output() {
+    let config = XFlateConfig {
+        checksum: true,
+        ..XFlateConfig::default()
+    };
+    let mut compressed = common::compress(&config, &large_document());
+    // The checksum follows the flag and backend ID
+    compressed[2] ^= 0xFF;
+
+    let (out, result) =
+        read_all(XFlateAsyncReader::decompress(compressed.as_slice(), config)).await;
+
+    assert!(matches!(
+        xflate_error(result.unwrap_err()),
+        XFlateError::ChecksumMismatch { .. }
+    ));
+    assert!(out.is_empty());
+}
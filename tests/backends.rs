@@ -0,0 +1,57 @@
+mod common;
+
+use common::{XML, round_trip};
+use xflate::{XFlate, XFlateConfig, XFlateDictionary, XFlateError, XmlBBackend};
+
+#[test]
+fn round_trip_with_enabled_backends() {
+    for &xmlb_backend in XmlBBackend::ALL {
+        let config = XFlateConfig {
+            xmlb_backend,
+            ..XFlateConfig::default()
+        };
+
+        assert_eq!(round_trip(&config, XML), XML.trim_end(), "{}", xmlb_backend);
+    }
+}
+
+#[test]
+fn disabled_backends_are_an_error() {
+    let disabled = [XmlBBackend::Zstd, XmlBBackend::Brotli, XmlBBackend::Lzma]
+        .into_iter()
+        .filter(|backend| !XmlBBackend::ALL.contains(backend));
+
+    for xmlb_backend in disabled {
+        let config = XFlateConfig {
+            xmlb_backend,
+            ..XFlateConfig::default()
+        };
+
+        let result = XFlate::new(config).compress(XML.as_bytes());
+        assert!(
+            matches!(result, Err(XFlateError::XmlBError(_))),
+            "{}",
+            xmlb_backend
+        );
+    }
+}
+
+#[test]
+fn dictionary_needs_deflate() {
+    let dictionary = XFlateDictionary::train(&[XML]).unwrap();
+
+    for xmlb_backend in [XmlBBackend::Zstd, XmlBBackend::Brotli, XmlBBackend::Lzma] {
+        let config = XFlateConfig {
+            xmlb_backend,
+            dictionary: Some(dictionary.clone()),
+            ..XFlateConfig::default()
+        };
+
+        let result = XFlate::new(config).compress(XML.as_bytes());
+        assert!(
+            matches!(result, Err(XFlateError::XmlBError(_))),
+            "{}",
+            xmlb_backend
+        );
+    }
+}
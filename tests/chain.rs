@@ -0,0 +1,112 @@
+mod common;
+
+use common::XML;
+use xflate::{
+    XFlateError, XmlBChain, XmlBCompress, XmlBCompressor, XmlBDecompressor, XmlBDeflateBackend,
+    XmlBMoveToFront, XmlBRunLength,
+};
+
+/// Runs around the lengths the count byte covers
+fn runs() -> Vec<Vec<u8>> {
+    [0, 1, 3, 4, 5, 258, 259, 260, 1000]
+        .into_iter()
+        .map(|len| {
+            let mut data = b"xy".to_vec();
+            data.extend(std::iter::repeat_n(b'a', len));
+            data.extend(b"yx");
+            data
+        })
+        .collect()
+}
+
+fn chain() -> XmlBChain {
+    XmlBChain::new()
+        .then(XmlBMoveToFront)
+        .then(XmlBRunLength)
+        .then(XmlBDeflateBackend::new(XmlBCompress::Best))
+}
+
+#[test]
+fn move_to_front_round_trip() {
+    assert_eq!(XmlBMoveToFront.compress(b"aab").unwrap(), [97, 0, 98]);
+
+    for data in runs().into_iter().chain([XML.as_bytes().to_vec()]) {
+        let encoded = XmlBMoveToFront.compress(&data).unwrap();
+        assert_eq!(encoded.len(), data.len());
+        assert_eq!(XmlBMoveToFront.decompress(&encoded).unwrap(), data);
+    }
+}
+
+#[test]
+fn run_length_round_trip() {
+    for data in runs().into_iter().chain([XML.as_bytes().to_vec()]) {
+        let encoded = XmlBRunLength.compress(&data).unwrap();
+        assert_eq!(XmlBRunLength.decompress(&encoded).unwrap(), data);
+    }
+}
+
+#[test]
+fn run_length_counts() {
+    let encode = |len| XmlBRunLength.compress(&vec![b'a'; len]).unwrap();
+
+    assert_eq!(encode(3), b"aaa");
+    assert_eq!(encode(4), b"aaaa\x00");
+    assert_eq!(encode(5), b"aaaa\x01");
+    // The longest run a count covers, and one past it
+    assert_eq!(encode(259), b"aaaa\xff");
+    assert_eq!(encode(260), b"aaaa\xffa");
+    assert_eq!(encode(263), b"aaaa\xffaaaa\x00");
+}
+
+#[test]
+fn truncated_run_length_is_an_error() {
+    let result = XmlBRunLength.decompress(b"xaaaa");
+    assert!(matches!(result, Err(XFlateError::XmlBError(_))));
+}
+
+#[test]
+fn chain_round_trip() {
+    let chain = chain();
+    assert_eq!(chain.len(), 3);
+
+    for data in runs().into_iter().chain([XML.as_bytes().to_vec()]) {
+        let compressed = chain.compress(&data).unwrap();
+        assert_eq!(chain.decompress(&compressed).unwrap(), data);
+    }
+}
+
+#[test]
+fn empty_chain_passes_data_through() {
+    let chain = XmlBChain::new();
+    assert!(chain.is_empty());
+
+    assert_eq!(chain.compress(XML.as_bytes()).unwrap(), XML.as_bytes());
+    assert_eq!(chain.decompress(XML.as_bytes()).unwrap(), XML.as_bytes());
+}
+
+#[test]
+fn nested_chains_match_flat_ones() {
+    let nested = XmlBChain::new()
+        .then(XmlBChain::new().then(XmlBMoveToFront).then(XmlBRunLength))
+        .then(XmlBDeflateBackend::new(XmlBCompress::Best));
+
+    let compressed = nested.compress(XML.as_bytes()).unwrap();
+    assert_eq!(compressed, chain().compress(XML.as_bytes()).unwrap());
+    assert_eq!(nested.decompress(&compressed).unwrap(), XML.as_bytes());
+}
+
+#[test]
+fn decompress_limited_through_a_chain() {
+    let chain = chain();
+    let data = vec![b'a'; 100_000];
+    let compressed = chain.compress(&data).unwrap();
+
+    assert_eq!(
+        chain.decompress_limited(&compressed, data.len()).unwrap(),
+        data
+    );
+
+    // Runs shrink the data ahead of deflate, but every stage is limited
+    let result = chain.decompress_limited(&compressed, data.len() - 1);
+    assert!(matches!(result, Err(XFlateError::LimitExceeded(_))));
+}
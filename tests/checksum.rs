@@ -0,0 +1,63 @@
+mod common;
+
+use common::{XML, compress, encodings};
+use xflate::{XFlate, XFlateConfig, XFlateError};
+
+/// Outputs of every encoding, with the stored checksum altered
+fn tampered() -> Vec<(XFlateConfig, Vec<u8>)> {
+    encodings()
+        .into_iter()
+        .map(|config| {
+            let config = XFlateConfig {
+                checksum: true,
+                ..config
+            };
+            let mut out = compress(&config, XML);
+            // The checksum follows the flag and backend ID
+            out[2] ^= 0xFF;
+            (config, out)
+        })
+        .collect()
+}
+
+#[test]
+fn intact_checksum_passes() {
+    for (config, mut out) in tampered() {
+        out[2] ^= 0xFF;
+        XFlate::new(config).decompress(out.as_slice()).unwrap();
+    }
+}
+
+#[test]
+fn decompress_detects_mismatch() {
+    for (config, out) in tampered() {
+        let result = XFlate::new(config).decompress(out.as_slice());
+        assert!(matches!(result, Err(XFlateError::ChecksumMismatch { .. })));
+    }
+}
+
+#[test]
+fn events_detect_mismatch() {
+    for (config, out) in tampered() {
+        let result = XFlate::new(config)
+            .decompress_events(out.as_slice())
+            .and_then(|events| events.collect::<Result<Vec<_>, _>>());
+        assert!(matches!(result, Err(XFlateError::ChecksumMismatch { .. })));
+    }
+}
+
+#[test]
+fn document_detects_mismatch() {
+    for (config, out) in tampered() {
+        let result = XFlate::new(config).decompress_document(out.as_slice());
+        assert!(matches!(result, Err(XFlateError::ChecksumMismatch { .. })));
+    }
+}
+
+#[test]
+fn missing_checksum_is_an_error() {
+    for (config, out) in tampered() {
+        let result = XFlate::new(config).decompress(&out[..4]);
+        assert!(matches!(result, Err(XFlateError::FormatError(_))));
+    }
+}
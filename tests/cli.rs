@@ -0,0 +1,90 @@
+mod common;
+
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+use common::{XML, compress};
+use xflate::{XFlate, XFlateConfig};
+
+/// Run the binary with `args`, feeding `stdin` to it
+fn xflate(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_xflate"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Path in the directory Cargo keeps for integration tests
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn round_trip_through_stdin_and_stdout() {
+    let compressed = xflate(&["compress", "--symbol-size", "2"], XML.as_bytes());
+    assert!(compressed.status.success());
+    assert_eq!(compressed.stdout, compress(&XFlateConfig::default(), XML));
+
+    let decompressed = xflate(&["decompress"], &compressed.stdout);
+    assert!(decompressed.status.success());
+    assert_eq!(decompressed.stdout, XML.trim_end().as_bytes());
+    assert!(decompressed.stderr.is_empty());
+}
+
+#[test]
+fn bad_input_file_is_reported_on_stderr() {
+    let bad = b"\x07\x00 not compressed";
+    let path = temp_file("cli_bad_input.xf", bad);
+
+    let output = xflate(&["decompress", path.to_str().unwrap()], &[]);
+    let err = XFlate::new(XFlateConfig::default())
+        .decompress(bad.as_slice())
+        .unwrap_err();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("xflate: {}\n", err)
+    );
+}
+
+#[test]
+fn missing_input_file_is_reported_on_stderr() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli_missing.xf");
+    let output = xflate(&["inspect", path.to_str().unwrap()], &[]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with(&format!("xflate: {}: ", path.display())),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn unknown_option_prints_the_usage() {
+    let output = xflate(&["compress", "--nope"], &[]);
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("xflate: Unknown option: --nope\n"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("Usage: xflate"));
+}
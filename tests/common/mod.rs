@@ -0,0 +1,82 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use std::fmt::Debug;
+
+use xflate::{
+    XFlate, XFlateConfig, XFlateError, XFlateLimits, XFlateMode, XmlBCompress, XmlBCompressor,
+    XmlBDeflateBackend, XmlNSymbolTable, XmlNTagTable, encode_xmls,
+};
+
+pub const XML: &str = include_str!("../data/basic.xml");
+
+/// A config for each encoding: XMLN, lossless XMLN and raw
+pub fn encodings() -> [XFlateConfig; 3] {
+    [
+        XFlateConfig::default(),
+        XFlateConfig {
+            lossless: true,
+            ..XFlateConfig::default()
+        },
+        XFlateConfig {
+            mode: XFlateMode::Raw,
+            ..XFlateConfig::default()
+        },
+    ]
+}
+
+/// Default config with the given limits
+pub fn limited(limits: XFlateLimits) -> XFlateConfig {
+    XFlateConfig {
+        limits,
+        ..XFlateConfig::default()
+    }
+}
+
+pub fn compress(config: &XFlateConfig, xml: &str) -> Vec<u8> {
+    XFlate::new(config.clone())
+        .compress(xml.as_bytes())
+        .unwrap()
+}
+
+pub fn decompress(config: &XFlateConfig, out: &[u8]) -> Result<String, XFlateError> {
+    XFlate::new(config.clone()).decompress(out)
+}
+
+/// Compress and decompress `xml` with the same config
+pub fn round_trip(config: &XFlateConfig, xml: &str) -> String {
+    decompress(config, &compress(config, xml)).unwrap()
+}
+
+#[track_caller]
+pub fn assert_limit_exceeded<T: Debug>(result: Result<T, XFlateError>) {
+    assert!(
+        matches!(result, Err(XFlateError::LimitExceeded(_))),
+        "{:?}",
+        result
+    );
+}
+
+/// Frame a payload as the default config would
+pub fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![0, 0];
+    out.extend(
+        XmlBDeflateBackend::new(XmlBCompress::Best)
+            .compress(payload)
+            .unwrap(),
+    );
+    out
+}
+
+/// Frame XMLN written by hand, after the headers of the tables
+pub fn frame_xmln<S, T>(sym_table: &S, tag_table: &T, xmln: &str) -> Vec<u8>
+where
+    S: XmlNSymbolTable,
+    T: XmlNTagTable,
+{
+    let mut payload = tag_table.to_header();
+    payload.extend(sym_table.to_header());
+    payload.extend(encode_xmls(xmln).unwrap());
+
+    frame(&payload)
+}
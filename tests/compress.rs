@@ -0,0 +1,77 @@
+mod common;
+
+use common::{decompress, round_trip};
+use xflate::{XFlate, XFlateConfig, XFlateError, XFlateMode};
+use xml::{name::OwnedName, namespace::Namespace, reader::XmlEvent};
+
+const CDATA: &str = "<root><![CDATA[a < b]]></root>";
+const INSTRUCTION: &str = "<root><?target data?></root>";
+
+fn root_events(inner: XmlEvent) -> Vec<XmlEvent> {
+    vec![
+        XmlEvent::StartElement {
+            name: OwnedName::local("root"),
+            attributes: Vec::new(),
+            namespace: Namespace::empty(),
+        },
+        inner,
+        XmlEvent::EndElement {
+            name: OwnedName::local("root"),
+        },
+    ]
+}
+
+#[test]
+fn xmln_rejects_unsupported_markup() {
+    for xml in [CDATA, INSTRUCTION] {
+        for verify in [false, true] {
+            let config = XFlateConfig {
+                verify,
+                ..XFlateConfig::default()
+            };
+            let result = XFlate::new(config).compress(xml.as_bytes());
+            assert!(matches!(result, Err(XFlateError::XmlNError(_))), "{}", xml);
+        }
+    }
+}
+
+#[test]
+fn auto_falls_back_to_raw_on_unsupported_markup() {
+    for xml in [CDATA, INSTRUCTION] {
+        for verify in [false, true] {
+            let config = XFlateConfig {
+                mode: XFlateMode::Auto,
+                verify,
+                ..XFlateConfig::default()
+            };
+
+            assert_eq!(round_trip(&config, xml), xml);
+        }
+    }
+}
+
+#[test]
+fn events_reject_unsupported_markup() {
+    let events = [
+        XmlEvent::CData("a < b".to_string()),
+        XmlEvent::Comment("note".to_string()),
+        XmlEvent::ProcessingInstruction {
+            name: "target".to_string(),
+            data: None,
+        },
+    ];
+
+    for event in events {
+        let result = XFlate::new(XFlateConfig::default()).compress_events(root_events(event));
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn best_tries_the_backend_alone_on_unsupported_markup() {
+    let mut xflate = XFlate::new(XFlateConfig::default());
+    let out = xflate.compress_best(CDATA.as_bytes()).unwrap();
+
+    assert_eq!(xflate.config().mode, XFlateMode::Raw);
+    assert_eq!(decompress(&XFlateConfig::default(), &out).unwrap(), CDATA);
+}
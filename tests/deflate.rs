@@ -0,0 +1,64 @@
+mod common;
+
+use common::{XML, round_trip};
+use xflate::{XFlate, XFlateConfig, XFlateError, XmlBDeflateFraming};
+
+#[test]
+fn framings_round_trip() {
+    for xmlb_framing in [
+        XmlBDeflateFraming::Raw,
+        XmlBDeflateFraming::Zlib,
+        XmlBDeflateFraming::Gzip,
+    ] {
+        let config = XFlateConfig {
+            xmlb_framing,
+            ..XFlateConfig::default()
+        };
+
+        assert_eq!(
+            round_trip(&config, XML),
+            XML.trim_end(),
+            "{:?}",
+            xmlb_framing
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "fast")]
+fn window_bits_round_trip() {
+    for bits in [9, 12, 15] {
+        let config = XFlateConfig {
+            xmlb_window_bits: Some(bits),
+            ..XFlateConfig::default()
+        };
+
+        assert_eq!(round_trip(&config, XML), XML.trim_end(), "{}", bits);
+    }
+}
+
+#[test]
+#[cfg(feature = "fast")]
+fn invalid_window_bits_are_an_error() {
+    for bits in [8, 16] {
+        let config = XFlateConfig {
+            xmlb_window_bits: Some(bits),
+            ..XFlateConfig::default()
+        };
+
+        let result = XFlate::new(config).compress(XML.as_bytes());
+        assert!(matches!(result, Err(XFlateError::XmlBError(_))), "{}", bits);
+    }
+}
+
+#[test]
+#[cfg(not(feature = "fast"))]
+fn window_bits_need_the_fast_feature() {
+    let config = XFlateConfig {
+        xmlb_window_bits: Some(12),
+        ..XFlateConfig::default()
+    };
+
+    let result = XFlate::new(config).compress(XML.as_bytes());
+    assert!(matches!(result, Err(XFlateError::XmlBError(_))));
+}
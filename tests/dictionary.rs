@@ -0,0 +1,54 @@
+#![cfg(feature = "fast")]
+
+mod common;
+
+use common::{XML, compress, decompress, round_trip};
+use xflate::{XFlateConfig, XFlateDictionary};
+
+const SAMPLES: [&str; 3] = [
+    "<log><entry level=\"info\">abc</entry></log>",
+    "<log><entry level=\"warn\">cab</entry></log>",
+    "<log><entry level=\"info\">bca</entry></log>",
+];
+
+fn with_dictionary(dictionary: XFlateDictionary) -> XFlateConfig {
+    XFlateConfig {
+        dictionary: Some(dictionary),
+        ..XFlateConfig::default()
+    }
+}
+
+#[test]
+fn round_trip_of_a_training_sample() {
+    let config = with_dictionary(XFlateDictionary::train(&SAMPLES).unwrap());
+
+    for sample in SAMPLES {
+        assert_eq!(round_trip(&config, sample), sample);
+    }
+}
+
+#[test]
+fn round_trip_of_unseen_documents() {
+    let config = with_dictionary(XFlateDictionary::train(&SAMPLES).unwrap());
+
+    // New tags and symbols, within and beyond the room the table has
+    let documents = [
+        "<log><entry level=\"info\">abz</entry><extra/></log>",
+        "<log><entry level=\"error\">The quick brown fox, 42!</entry></log>",
+        XML.trim_end(),
+    ];
+
+    for xml in documents {
+        assert_eq!(round_trip(&config, xml), xml);
+    }
+}
+
+#[test]
+fn round_trip_with_a_stored_dictionary() {
+    let dictionary = XFlateDictionary::train(&SAMPLES).unwrap();
+    let stored = XFlateDictionary::from_bytes(&dictionary.to_bytes()).unwrap();
+    let xml = "<log><entry level=\"debug\">zzz</entry></log>";
+
+    let out = compress(&with_dictionary(dictionary), xml);
+    assert_eq!(decompress(&with_dictionary(stored), &out).unwrap(), xml);
+}
@@ -0,0 +1,119 @@
+use xflate::{XFlate, XFlateConfig, XFlateDocument};
+
+const XML: &str = r#"<?xml version="1.0"?>
+<feed xmlns="urn:atom" xmlns:m="urn:media">
+  <title type="text">News &amp; more</title>
+  <entry id="1">
+    <m:thumbnail m:url="a.png" width="10"/>
+    <summary>First <b>bold</b> line</summary>
+  </entry>
+  <entry id="2"/>
+</feed>
+"#;
+
+fn decompress_document(xml: &str) -> XFlateDocument {
+    let config = XFlateConfig::default();
+    let out = XFlate::new(config.clone())
+        .compress(xml.as_bytes())
+        .unwrap();
+
+    XFlate::new(config)
+        .decompress_document(out.as_slice())
+        .unwrap()
+}
+
+#[test]
+fn names_are_resolved() {
+    let document = decompress_document(XML);
+    let root = &document.root;
+
+    assert_eq!(document.doctype, None);
+    assert_eq!(root.name.local_name, "feed");
+    assert_eq!(root.name.prefix, None);
+    assert_eq!(root.name.namespace.as_deref(), Some("urn:atom"));
+
+    let thumbnail = root
+        .child("entry")
+        .and_then(|entry| entry.child("m:thumbnail"))
+        .unwrap();
+    assert_eq!(thumbnail.name.namespace.as_deref(), Some("urn:media"));
+    assert_eq!(thumbnail.namespace.get("m"), Some("urn:media"));
+    assert_eq!(thumbnail.namespace.get(""), Some("urn:atom"));
+}
+
+#[test]
+fn children_and_attributes() {
+    let document = decompress_document(XML);
+    let root = &document.root;
+
+    let title = root.child("title").unwrap();
+    assert_eq!(title.attribute("type"), Some("text"));
+    assert_eq!(title.text(), "News & more");
+
+    let ids = root
+        .children_named("entry")
+        .map(|entry| entry.attribute("id"))
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [Some("1"), Some("2")]);
+
+    let entry = root.child("entry").unwrap();
+    assert_eq!(
+        entry
+            .elements()
+            .map(|e| e.name.to_string())
+            .collect::<Vec<_>>(),
+        ["{urn:media}m:thumbnail", "{urn:atom}summary"]
+    );
+    assert_eq!(entry.child("summary").unwrap().text(), "First bold line");
+
+    // A name without a prefix matches the local name
+    let thumbnail = entry.child("thumbnail").unwrap();
+    assert_eq!(thumbnail.attribute("m:url"), Some("a.png"));
+    assert_eq!(thumbnail.attribute("url"), Some("a.png"));
+    assert_eq!(thumbnail.attribute("width"), Some("10"));
+}
+
+#[test]
+fn missing_children_and_attributes() {
+    let document = decompress_document(XML);
+    let root = &document.root;
+
+    assert!(root.child("missing").is_none());
+    assert!(root.child("m:title").is_none());
+    assert!(root.child("summary").is_none(), "only direct children");
+    assert_eq!(root.attribute("id"), None);
+
+    let empty = root.children_named("entry").nth(1).unwrap();
+    assert!(empty.child("summary").is_none());
+    assert_eq!(empty.elements().count(), 0);
+    assert_eq!(empty.text(), "");
+
+    let thumbnail = root
+        .child("entry")
+        .and_then(|entry| entry.child("thumbnail"))
+        .unwrap();
+    assert_eq!(thumbnail.attribute("height"), None);
+    assert_eq!(thumbnail.attribute("x:url"), None);
+    assert_eq!(thumbnail.attribute("m:width"), None);
+}
+
+#[test]
+fn edited_document_round_trip() {
+    let mut document = decompress_document(XML);
+    let entry = document.root.child_mut("entry").unwrap();
+    entry.set_attribute("id", "3");
+    entry.set_attribute("lang", "en");
+
+    let config = XFlateConfig::default();
+    let out = XFlate::new(config.clone())
+        .compress_document(&document)
+        .unwrap();
+    let decompressed = XFlate::new(config)
+        .decompress_document(out.as_slice())
+        .unwrap();
+
+    assert_eq!(decompressed, document);
+    let entry = decompressed.root.child("entry").unwrap();
+    assert_eq!(entry.attribute("id"), Some("3"));
+    assert_eq!(entry.attribute("lang"), Some("en"));
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::round_trip;
+use xflate::{
+    XFlateConfig, XmlNDynamicSymbolTable, XmlNDynamicTagTable, XmlNSymbolTable, decode_xmln,
+    encode_xmln,
+};
+
+const XML: &str = r#"<r><a x="1"></a><b/><c> </c></r>"#;
+
+fn tables() -> (XmlNDynamicSymbolTable, XmlNDynamicTagTable) {
+    (
+        XmlNDynamicSymbolTable::new(2),
+        XmlNDynamicTagTable::default(),
+    )
+}
+
+#[test]
+fn empty_elements_get_the_self_closing_token() {
+    let (mut sym_table, mut tag_table) = tables();
+    let xmln = encode_xmln(XML.as_bytes(), &mut sym_table, &mut tag_table).unwrap();
+
+    // Whitespace is content, so `c` keeps its closing token
+    assert_eq!(xmln, "T0 T1 A2 01 S T3 S T4 02 0 0");
+    assert_eq!(
+        decode_xmln(&xmln, &mut sym_table, &mut tag_table).unwrap(),
+        r#"<r><a x="1"/><b/><c> </c></r>"#
+    );
+}
+
+#[test]
+fn closing_token_is_written_as_an_end_tag() {
+    let (mut sym_table, mut tag_table) = tables();
+    encode_xmln(XML.as_bytes(), &mut sym_table, &mut tag_table).unwrap();
+
+    assert_eq!(
+        decode_xmln("T0 T1 0 T3 S 0", &mut sym_table, &mut tag_table).unwrap(),
+        "<r><a></a><b/></r>"
+    );
+}
+
+#[test]
+fn decompressed_empty_elements_are_self_closed() {
+    assert_eq!(
+        round_trip(&XFlateConfig::default(), XML),
+        r#"<r><a x="1"/><b/><c> </c></r>"#
+    );
+}
+
+#[test]
+fn lossless_keeps_end_tags() {
+    let config = XFlateConfig {
+        lossless: true,
+        ..XFlateConfig::default()
+    };
+
+    assert_eq!(round_trip(&config, XML), XML);
+}
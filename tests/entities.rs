@@ -0,0 +1,73 @@
+mod common;
+
+use common::{decompress, round_trip};
+use xflate::{XFlate, XFlateConfig, XFlateError, XmlNEntities};
+use xml::{name::OwnedName, namespace::Namespace, reader::XmlEvent};
+
+const DOCTYPE: &str = "<!DOCTYPE a [<!ENTITY e \"entity\">]>";
+
+fn text_events(text: &str) -> Vec<XmlEvent> {
+    vec![
+        XmlEvent::StartElement {
+            name: OwnedName::local("a"),
+            attributes: Vec::new(),
+            namespace: Namespace::empty(),
+        },
+        XmlEvent::Characters(text.to_string()),
+        XmlEvent::EndElement {
+            name: OwnedName::local("a"),
+        },
+    ]
+}
+
+#[test]
+fn references_are_expanded_by_default() {
+    let xml = format!("{}<a>&e; &amp;</a>", DOCTYPE);
+
+    assert_eq!(
+        round_trip(&XFlateConfig::default(), &xml),
+        format!("{}<a>entity &amp;</a>", DOCTYPE)
+    );
+}
+
+#[test]
+fn references_are_preserved() {
+    let xml = format!("{}<a x=\"&e;\">&e; &amp; &e;</a>", DOCTYPE);
+    let config = XFlateConfig {
+        entities: XmlNEntities::Preserve,
+        ..XFlateConfig::default()
+    };
+
+    assert_eq!(round_trip(&config, &xml), xml);
+}
+
+#[test]
+fn noncharacter_in_events_is_an_error() {
+    let result = XFlate::new(XFlateConfig::default()).compress_events(text_events("a\u{FFFF}b"));
+    assert!(
+        matches!(result, Err(XFlateError::XmlNError(_))),
+        "{:?}",
+        result
+    );
+
+    let out = XFlate::new(XFlateConfig::default())
+        .compress_events(text_events("a&b"))
+        .unwrap();
+    assert_eq!(
+        decompress(&XFlateConfig::default(), &out).unwrap(),
+        "<a>a&amp;b</a>"
+    );
+}
+
+#[test]
+fn noncharacter_in_the_document_is_an_error() {
+    let result = XFlate::new(XFlateConfig::default()).compress("<a>a\u{FFFF}b</a>".as_bytes());
+    assert!(result.is_err());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn noncharacter_in_serialized_values_is_an_error() {
+    assert!(xflate::to_vec("a\u{FFFF}b").is_err());
+    assert!(xflate::to_vec(&vec!["\u{FFFF}"]).is_err());
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use common::XML;
+use xflate::{XFlate, XFlateConfig};
+
+const TABS: &str = "<a>\n\t<b x=\"1\">\n\t\t<c>text</c>\n\t\t<d/>\n\t  <e>odd</e>\n\t</b>\n</a>";
+
+#[test]
+fn xmln_estimate_matches_the_encoder() {
+    for xml in [XML, TABS] {
+        let estimate = XFlateConfig::estimate(xml.as_bytes()).unwrap();
+        let config = XFlateConfig {
+            symbol_size: estimate.symbol_size.symbol_size,
+            ..XFlateConfig::default()
+        };
+        let (_, stats) = XFlate::new(config)
+            .compress_with_stats(xml.as_bytes())
+            .unwrap();
+
+        assert_eq!(estimate.xmln_bytes, stats.xmln_bytes);
+    }
+}
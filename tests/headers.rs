@@ -0,0 +1,109 @@
+use xflate::{
+    XFlateError, XmlNDynamicSymbolTable, XmlNDynamicTagTable, XmlNSymbolTable, XmlNTagTable,
+};
+
+fn tag_table() -> XmlNDynamicTagTable {
+    let mut table = XmlNDynamicTagTable::default();
+    for tag in ["root", "a b", "back\\slash", "ünïcode"] {
+        table.encode(tag);
+    }
+    table
+}
+
+fn sym_table() -> XmlNDynamicSymbolTable {
+    let mut table = XmlNDynamicSymbolTable::new(2);
+    for symbol in ['a', ' ', 'ß', '€'] {
+        table.encode(symbol);
+    }
+    table
+}
+
+fn tags(table: &XmlNDynamicTagTable) -> Vec<String> {
+    table.iter_tags().map(str::to_string).collect()
+}
+
+#[test]
+fn tag_header_round_trip() {
+    let table = tag_table();
+    let mut header = table.to_header();
+    let len = header.len();
+    header.extend(b"rest");
+
+    let (parsed, read) = XmlNDynamicTagTable::from_header(&header).unwrap();
+
+    assert_eq!(read, len);
+    assert_eq!(tags(&parsed), tags(&table));
+}
+
+#[test]
+fn symbol_header_round_trip() {
+    let table = sym_table();
+    let mut header = table.to_header();
+    let len = header.len();
+    header.extend(b"rest");
+
+    let (parsed, read) = XmlNDynamicSymbolTable::from_header(&header).unwrap();
+
+    assert_eq!(read, len);
+    assert_eq!(parsed.code_size(), table.code_size());
+    assert_eq!(
+        parsed.iter_symbols().collect::<Vec<_>>(),
+        table.iter_symbols().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn truncated_headers_are_errors() {
+    let header = tag_table().to_header();
+    for len in 0..header.len() {
+        assert!(XmlNDynamicTagTable::from_header(&header[..len]).is_err());
+    }
+
+    let header = sym_table().to_header();
+    for len in 0..header.len() {
+        assert!(XmlNDynamicSymbolTable::from_header(&header[..len]).is_err());
+    }
+}
+
+#[test]
+fn malformed_tag_headers_are_errors() {
+    let headers: [&[u8]; 6] = [
+        b"C 1 a ",
+        b"E1 a ",
+        b"E x a ",
+        b"E 2 a a ",
+        b"E 99999999999999999999 a ",
+        b"E 1 \xFF ",
+    ];
+
+    for header in headers {
+        let result = XmlNDynamicTagTable::from_header(header);
+        assert!(
+            matches!(result, Err(XFlateError::FormatError(_))),
+            "{:?}",
+            String::from_utf8_lossy(header)
+        );
+    }
+}
+
+#[test]
+fn malformed_symbol_headers_are_errors() {
+    let headers: [&[u8]; 7] = [
+        b"E 1 1 a ",
+        b"C 0 1 a ",
+        b"C 20 1 a ",
+        b"C 1 10 abcdefghij ",
+        b"C 1 2 aa ",
+        b"C 1 1 \xFF ",
+        b"C 1 1 a",
+    ];
+
+    for header in headers {
+        let result = XmlNDynamicSymbolTable::from_header(header);
+        assert!(
+            matches!(result, Err(XFlateError::FormatError(_))),
+            "{:?}",
+            String::from_utf8_lossy(header)
+        );
+    }
+}
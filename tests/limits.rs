@@ -0,0 +1,118 @@
+mod common;
+
+use common::{XML, assert_limit_exceeded, compress, decompress, encodings, frame_xmln, limited};
+use xflate::{
+    XFlate, XFlateConfig, XFlateLimits, XmlNDynamicSymbolTable, XmlNDynamicTagTable,
+    XmlNSymbolTable, XmlNTagTable,
+};
+
+#[test]
+fn default_limits_allow_ordinary_documents() {
+    for config in encodings() {
+        decompress(&XFlateConfig::default(), &compress(&config, XML)).unwrap();
+    }
+}
+
+#[test]
+fn output_over_the_limit() {
+    let limits = XFlateLimits {
+        max_output_bytes: 100,
+        ..XFlateLimits::default()
+    };
+
+    for config in encodings() {
+        assert_limit_exceeded(decompress(&limited(limits), &compress(&config, XML)));
+    }
+}
+
+#[test]
+fn depth_over_the_limit() {
+    let xml = "<a>".repeat(10) + &"</a>".repeat(10);
+    let out = compress(&XFlateConfig::default(), &xml);
+
+    let limits = XFlateLimits {
+        max_depth: 10,
+        ..XFlateLimits::default()
+    };
+    decompress(&limited(limits), &out).unwrap();
+
+    let limits = XFlateLimits {
+        max_depth: 9,
+        ..XFlateLimits::default()
+    };
+    assert_limit_exceeded(decompress(&limited(limits), &out));
+}
+
+#[test]
+fn table_entries_over_the_limit() {
+    let xml = (0..10).map(|i| format!("<t{}/>", i)).collect::<String>();
+    let out = compress(&XFlateConfig::default(), &format!("<root>{}</root>", xml));
+
+    let limits = XFlateLimits {
+        max_table_entries: 8,
+        ..XFlateLimits::default()
+    };
+    assert_limit_exceeded(decompress(&limited(limits), &out));
+}
+
+#[test]
+fn header_over_the_limit() {
+    let out = compress(&XFlateConfig::default(), XML);
+
+    let limits = XFlateLimits {
+        max_header_bytes: 16,
+        ..XFlateLimits::default()
+    };
+    assert_limit_exceeded(decompress(&limited(limits), &out));
+}
+
+#[test]
+fn unlimited_allows_everything() {
+    let config = XFlateConfig {
+        lossless: true,
+        ..XFlateConfig::default()
+    };
+    let out = compress(&config, XML);
+
+    assert_eq!(
+        decompress(&limited(XFlateLimits::unlimited()), &out).unwrap(),
+        XML
+    );
+}
+
+/// A small frame holding 1000 indentation tokens 1000 elements deep,
+/// which expand to about 2MB of whitespace
+fn inflating_frame() -> Vec<u8> {
+    let mut sym_table = XmlNDynamicSymbolTable::new(2);
+    sym_table.encode('\n');
+    sym_table.encode(' ');
+    let mut tag_table = XmlNDynamicTagTable::default();
+    tag_table.encode("a");
+
+    let xmln = "T0 ".repeat(1000) + "I0102 " + &"I ".repeat(999) + &"0 ".repeat(1000);
+    frame_xmln(&sym_table, &tag_table, xmln.trim_end())
+}
+
+#[test]
+fn indentation_over_the_output_limit() {
+    let out = inflating_frame();
+    let limits = XFlateLimits {
+        max_output_bytes: 10000,
+        ..XFlateLimits::default()
+    };
+    let mut xflate = XFlate::new(limited(limits));
+
+    assert_limit_exceeded(xflate.decompress(out.as_slice()));
+    assert_limit_exceeded(xflate.decompress_document(out.as_slice()));
+    assert_limit_exceeded(
+        xflate
+            .decompress_events(out.as_slice())
+            .and_then(|events| events.collect::<Result<Vec<_>, _>>()),
+    );
+
+    // Within the limit, the events decode in full
+    let events = XFlate::new(XFlateConfig::default())
+        .decompress_events(out.as_slice())
+        .unwrap();
+    assert!(events.collect::<Result<Vec<_>, _>>().is_ok());
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{XML, compress, decompress, round_trip};
+use xflate::XFlateConfig;
+
+/// Documents with the details a parser discards
+const DOCUMENTS: &[&str] = &[
+    XML,
+    "<?xml version='1.0' encoding=\"UTF-8\"?>\r\n<a  x = 'single'\ty=\"double\"/>\r\n",
+    "<a><b></b><c/><d   /></a>",
+    "<!DOCTYPE a [<!ENTITY e \"entity\">]>\n<a>&e; &amp; &#65; &#x42; &lt;</a>",
+    "<a>\n  <!-- comment -->\n  <?target data?>\n  <![CDATA[<raw> & text]]>\n</a>\n\n",
+    "<p:a xmlns:p=\"urn:p\" xmlns=\"urn:d\"><b p:x=\"1\"/></p:a>",
+    "<a xml:space=\"preserve\">  <b> spaced </b>\t</a>",
+    "<a>ünïcödé €</a>",
+];
+
+#[test]
+fn restores_the_original_bytes() {
+    let config = XFlateConfig {
+        lossless: true,
+        ..XFlateConfig::default()
+    };
+
+    for xml in DOCUMENTS {
+        let out = compress(&config, xml);
+        assert_eq!(&decompress(&XFlateConfig::default(), &out).unwrap(), xml);
+    }
+}
+
+#[test]
+fn restores_with_every_option() {
+    for xml in DOCUMENTS {
+        for (indentation, symbol_size) in [(false, 2), (true, 3)] {
+            let config = XFlateConfig {
+                lossless: true,
+                checksum: true,
+                indentation,
+                symbol_size,
+                ..XFlateConfig::default()
+            };
+
+            assert_eq!(&round_trip(&config, xml), xml);
+        }
+    }
+}
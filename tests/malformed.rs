@@ -0,0 +1,122 @@
+//! Corrupt and crafted input must make decompression fail, never panic
+
+mod common;
+
+use common::{compress, encodings, frame, frame_xmln};
+use xflate::{
+    XFlate, XFlateConfig, XFlateOutput, XmlBCompress, XmlBDecompressor, XmlBDeflateBackend,
+    XmlNDynamicSymbolTable, XmlNDynamicTagTable, XmlNSymbolTable, XmlNTagTable,
+};
+
+const XML: &str =
+    "<!DOCTYPE r [<!ENTITY e \"x\">]>\n<r a=\"1\">\n  <b>t &amp; &e;</b>\n  <c/>\n</r>";
+
+/// Every encoding, and one storing a checksum
+fn configs() -> impl Iterator<Item = XFlateConfig> {
+    let checksum = XFlateConfig {
+        checksum: true,
+        ..XFlateConfig::default()
+    };
+
+    encodings().into_iter().chain([checksum])
+}
+
+/// Run every way of reading `bytes`, ignoring the outcome
+fn read_all(config: &XFlateConfig, bytes: &[u8]) {
+    let _ = XFlate::new(config.clone()).decompress(bytes);
+    let _ = XFlate::new(config.clone()).decompress_with(bytes, &XFlateOutput::Canonical);
+    let _ = XFlate::new(config.clone()).decompress_document(bytes);
+    let _ = XFlate::new(config.clone()).inspect(bytes);
+    if let Ok(events) = XFlate::new(config.clone()).decompress_events(bytes) {
+        events.for_each(drop);
+    }
+}
+
+#[test]
+fn truncated_output() {
+    for config in configs() {
+        let out = compress(&config, XML);
+        for len in 0..out.len() {
+            read_all(&config, &out[..len]);
+        }
+    }
+}
+
+#[test]
+fn corrupt_output() {
+    for config in configs() {
+        let out = compress(&config, XML);
+        for i in 0..out.len() {
+            for mask in [0x01, 0x10, 0x80, 0xFF] {
+                let mut corrupt = out.clone();
+                corrupt[i] ^= mask;
+                read_all(&config, &corrupt);
+            }
+        }
+    }
+}
+
+#[test]
+fn corrupt_payload() {
+    // Corrupting the payload before the backend reaches the headers and XMLS
+    for lossless in [false, true] {
+        let config = XFlateConfig {
+            lossless,
+            ..XFlateConfig::default()
+        };
+        let out = compress(&config, XML);
+        let payload = XmlBDeflateBackend::new(XmlBCompress::Best)
+            .decompress(&out[2..])
+            .unwrap();
+
+        for i in 0..payload.len() {
+            for mask in [0x01, 0x0F, 0x10, 0xF0, 0xFF] {
+                let mut corrupt = payload.clone();
+                corrupt[i] ^= mask;
+
+                let mut framed = frame(&corrupt);
+                framed[0] = out[0];
+                read_all(&config, &framed);
+            }
+            read_all(&config, &frame(&payload[..i]));
+        }
+    }
+}
+
+#[test]
+fn crafted_xmln() {
+    let mut sym_table = XmlNDynamicSymbolTable::new(2);
+    sym_table.encode('a');
+    let mut tag_table = XmlNDynamicTagTable::default();
+    tag_table.encode("r");
+    tag_table.encode("b");
+
+    let xmlns = [
+        "0",
+        "S",
+        "I",
+        "T0 010 0",
+        "T0 01010 0",
+        "T0 99 0",
+        "T7 0",
+        "T0 A7 01 0",
+        "T0 A1",
+        "T0 A1 01",
+        "T0 I",
+        "T0 I01 0",
+        "T0 I0101 I 0",
+        "T0 T1 0",
+        "T0 0 0",
+        "T0 S S",
+        "A1 01 T0 0",
+        "T0 01 T1 S 0 0",
+    ];
+
+    for xmln in xmlns {
+        let framed = frame_xmln(&sym_table, &tag_table, xmln);
+
+        let result = XFlate::new(XFlateConfig::default()).decompress(framed.as_slice());
+        assert!(result.is_err(), "{:?} decoded to {:?}", xmln, result);
+        read_all(&XFlateConfig::default(), &framed);
+    }
+}
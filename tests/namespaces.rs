@@ -0,0 +1,46 @@
+use xflate::{XFlate, XFlateConfig, XmlNTagTable};
+use xml::{EventReader, reader::XmlEvent};
+
+const XML: &str =
+    "<p:a xmlns:p=\"urn:p\" xmlns=\"urn:d\"><b p:x=\"1\"><p:c xmlns:p=\"urn:q\"/></b></p:a>";
+
+/// Start elements with their resolved names and attribute names
+fn names(xml: &str) -> Vec<(String, Vec<String>)> {
+    EventReader::from_str(xml)
+        .into_iter()
+        .filter_map(|e| match e.unwrap() {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => Some((
+                name.to_string(),
+                attributes
+                    .iter()
+                    .map(|attr| attr.name.to_string())
+                    .collect(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn names_and_declarations_are_stored_as_written() {
+    let mut xflate = XFlate::new(XFlateConfig::default());
+    let out = xflate.compress(XML.as_bytes()).unwrap();
+    xflate.inspect(out.as_slice()).unwrap();
+
+    let tags = xflate.tag_table().iter_tags().collect::<Vec<_>>();
+    assert_eq!(tags, ["p:a", "xmlns", "xmlns:p", "b", "p:x", "p:c"]);
+}
+
+#[test]
+fn namespaces_survive_the_round_trip() {
+    let out = XFlate::new(XFlateConfig::default())
+        .compress(XML.as_bytes())
+        .unwrap();
+    let xml = XFlate::new(XFlateConfig::default())
+        .decompress(out.as_slice())
+        .unwrap();
+
+    assert_eq!(names(&xml), names(XML));
+}
@@ -0,0 +1,88 @@
+use xflate::{XFlate, XFlateConfig, XFlateOutput};
+
+const XML: &str = r#"<?xml version="1.0"?>
+<r xmlns:p="urn:p" b="2" a="1">
+  <p:x p:z="q" y="&quot;&#9;"/>
+  <t>keep  <i>this</i> &amp; more </t>
+  <s xml:space="preserve">
+    <e/>
+  </s>
+  <n></n>
+</r>
+"#;
+
+/// `XML` without the whitespace between elements
+const COMPACT: &str = r#"<r xmlns:p="urn:p" b="2" a="1"><p:x p:z="q" y="&quot;&#x9;"/><t>keep  <i>this</i> &amp; more </t><s xml:space="preserve">
+    <e/>
+  </s><n/></r>"#;
+
+fn decompress_with(xml: &str, output: &XFlateOutput) -> String {
+    let config = XFlateConfig::default();
+    let out = XFlate::new(config.clone())
+        .compress(xml.as_bytes())
+        .unwrap();
+
+    XFlate::new(config)
+        .decompress_with(out.as_slice(), output)
+        .unwrap()
+}
+
+#[test]
+fn compact_output() {
+    assert_eq!(decompress_with(XML, &XFlateOutput::Compact), COMPACT);
+    assert_eq!(decompress_with(COMPACT, &XFlateOutput::Compact), COMPACT);
+}
+
+#[test]
+fn indented_output() {
+    let expected = r#"<r xmlns:p="urn:p" b="2" a="1">
+    <p:x p:z="q" y="&quot;&#x9;"/>
+    <t>keep  <i>this</i> &amp; more </t>
+    <s xml:space="preserve">
+    <e/>
+  </s>
+    <n/>
+</r>"#;
+
+    assert_eq!(
+        decompress_with(COMPACT, &XFlateOutput::indented(4)),
+        expected
+    );
+    assert_eq!(decompress_with(XML, &XFlateOutput::indented(4)), expected);
+
+    let tabs = XFlateOutput::Indented {
+        indent: "\t".to_string(),
+    };
+    assert_eq!(
+        decompress_with(COMPACT, &tabs),
+        "<r xmlns:p=\"urn:p\" b=\"2\" a=\"1\">\n\t<p:x p:z=\"q\" y=\"&quot;&#x9;\"/>\n\t\
+         <t>keep  <i>this</i> &amp; more </t>\n\t<s xml:space=\"preserve\">\n    <e/>\n  </s>\n\t\
+         <n/>\n</r>"
+    );
+}
+
+#[test]
+fn canonical_output() {
+    // Attributes without a namespace sort first, and
+    // empty elements get an end tag
+    let expected = r#"<r xmlns:p="urn:p" a="1" b="2">
+  <p:x y="&quot;&#x9;" p:z="q"></p:x>
+  <t>keep  <i>this</i> &amp; more </t>
+  <s xml:space="preserve">
+    <e></e>
+  </s>
+  <n></n>
+</r>"#;
+
+    assert_eq!(decompress_with(XML, &XFlateOutput::Canonical), expected);
+}
+
+#[test]
+fn canonical_output_expands_entities() {
+    let xml = r#"<!DOCTYPE r [<!ENTITY e "value">]><r a="&e;">&e;</r>"#;
+
+    assert_eq!(
+        decompress_with(xml, &XFlateOutput::Canonical),
+        r#"<r a="value">value</r>"#
+    );
+}
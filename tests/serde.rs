@@ -0,0 +1,121 @@
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use xflate::XFlateError;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Kind {
+    Plain,
+    Sized(u32),
+    Point { x: i64, y: i64 },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Inner {
+    name: String,
+    ratio: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Record {
+    id: u64,
+    enabled: bool,
+    label: String,
+    note: Option<String>,
+    missing: Option<String>,
+    inner: Inner,
+    tags: Vec<String>,
+    counts: BTreeMap<String, u8>,
+    kinds: Vec<Kind>,
+    unit: (),
+    pair: (char, i8),
+}
+
+fn record() -> Record {
+    Record {
+        id: u64::MAX,
+        enabled: true,
+        label: "a < b & \"c\" ünï".to_string(),
+        note: Some("note".to_string()),
+        missing: None,
+        inner: Inner {
+            name: String::new(),
+            ratio: -0.25,
+        },
+        tags: vec!["x".to_string(), "y y".to_string()],
+        counts: BTreeMap::from([("one".to_string(), 1), ("two".to_string(), 2)]),
+        kinds: vec![Kind::Plain, Kind::Sized(7), Kind::Point { x: -1, y: 2 }],
+        unit: (),
+        pair: ('€', -8),
+    }
+}
+
+#[test]
+fn round_trip() {
+    let bytes = xflate::to_vec(&record()).unwrap();
+    let decoded: Record = xflate::from_slice(&bytes).unwrap();
+
+    assert_eq!(decoded, record());
+}
+
+#[test]
+fn round_trip_of_scalars_and_sequences() {
+    let bytes = xflate::to_vec(&vec![1u16, 2, 3]).unwrap();
+    assert_eq!(xflate::from_slice::<Vec<u16>>(&bytes).unwrap(), [1, 2, 3]);
+
+    let bytes = xflate::to_vec("text").unwrap();
+    assert_eq!(xflate::from_slice::<String>(&bytes).unwrap(), "text");
+}
+
+#[test]
+fn round_trip_of_none() {
+    let items = vec![Some(1u8), None, Some(2)];
+    let bytes = xflate::to_vec(&items).unwrap();
+    assert_eq!(
+        xflate::from_slice::<Vec<Option<u8>>>(&bytes).unwrap(),
+        items
+    );
+
+    let pair = (None::<u8>, 5u8);
+    let bytes = xflate::to_vec(&pair).unwrap();
+    assert_eq!(
+        xflate::from_slice::<(Option<u8>, u8)>(&bytes).unwrap(),
+        pair
+    );
+
+    let bytes = xflate::to_vec(&None::<u8>).unwrap();
+    assert_eq!(xflate::from_slice::<Option<u8>>(&bytes).unwrap(), None);
+
+    let map = BTreeMap::from([("a b".to_string(), None), ("c".to_string(), Some(1u8))]);
+    let bytes = xflate::to_vec(&map).unwrap();
+    assert_eq!(
+        xflate::from_slice::<BTreeMap<String, Option<u8>>>(&bytes).unwrap(),
+        map
+    );
+}
+
+#[test]
+fn invalid_names_are_an_error() {
+    #[derive(Serialize)]
+    struct Renamed {
+        #[serde(rename = "a b")]
+        spaced: u8,
+        #[serde(rename = "p:x")]
+        prefixed: u8,
+    }
+
+    let result = xflate::to_vec(&Renamed {
+        spaced: 1,
+        prefixed: 2,
+    });
+    assert!(matches!(result, Err(XFlateError::SerdeError(_))));
+}
+
+#[test]
+fn wrong_type_is_an_error() {
+    let bytes = xflate::to_vec(&record()).unwrap();
+    assert!(xflate::from_slice::<Inner>(&bytes).is_err());
+    assert!(xflate::from_slice::<Record>(&bytes[..bytes.len() / 2]).is_err());
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::round_trip;
+use xflate::{XFlate, XFlateConfig};
+
+/// Document holding exactly `n` distinct characters
+fn distinct_symbols(n: u32) -> String {
+    let text = (0..n)
+        .map(|i| char::from_u32(0x4E00 + i).unwrap())
+        .collect::<String>();
+    format!("<r>{}</r>", text)
+}
+
+#[test]
+fn round_trip_around_the_size_boundaries() {
+    // Code zero is reserved, so 9 and 99 characters fill sizes 1 and 2
+    for (n, symbol_size) in [(9, 1), (10, 2), (11, 2), (99, 2), (100, 3), (101, 3)] {
+        let xml = distinct_symbols(n);
+        let inferred = XFlateConfig::infer_symbol_size(xml.as_bytes()).unwrap();
+
+        assert_eq!(inferred.unique_symbols, n as usize);
+        assert_eq!(inferred.symbol_size, symbol_size, "{}", inferred);
+        assert!(inferred.capacity >= n as usize);
+
+        let config = XFlateConfig::from_xml(xml.as_bytes()).unwrap();
+        assert_eq!(round_trip(&config, &xml), xml, "{}", inferred);
+    }
+}
+
+#[test]
+fn one_size_smaller_is_an_error() {
+    for (n, symbol_size) in [(10, 1), (100, 2)] {
+        let config = XFlateConfig {
+            symbol_size,
+            ..XFlateConfig::default()
+        };
+        let result = XFlate::new(config).compress(distinct_symbols(n).as_bytes());
+
+        assert!(result.is_err(), "{} symbols at size {}", n, symbol_size);
+    }
+}
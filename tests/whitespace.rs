@@ -0,0 +1,55 @@
+mod common;
+
+use common::{decompress, round_trip};
+use xflate::{XFlate, XFlateConfig};
+use xml::{name::OwnedName, namespace::Namespace, reader::XmlEvent};
+
+/// `xml:space` set on `b`, inherited by `c` and overridden by `d`
+const SPACED: &str = "<a>\n  <b xml:space=\"preserve\">\n    <c> <g/> </c>\n    <d xml:space=\"default\">  <e/>  </d>\n  </b>\n  <f>  </f>\n</a>";
+
+#[test]
+fn whitespace_is_kept_by_default() {
+    assert_eq!(round_trip(&XFlateConfig::default(), SPACED), SPACED);
+}
+
+#[test]
+fn strip_keeps_preserved_whitespace() {
+    let config = XFlateConfig {
+        strip_whitespace: true,
+        ..XFlateConfig::default()
+    };
+
+    assert_eq!(
+        round_trip(&config, SPACED),
+        "<a><b xml:space=\"preserve\">\n    <c> <g/> </c>\n    <d xml:space=\"default\"><e/></d>\n  </b><f/></a>"
+    );
+}
+
+#[test]
+fn multibyte_whitespace_is_not_indentation() {
+    let names = ["a", "b", "c", "d"];
+    let mut events = Vec::new();
+    for name in names {
+        events.push(XmlEvent::StartElement {
+            name: OwnedName::local(name),
+            attributes: Vec::new(),
+            namespace: Namespace::empty(),
+        });
+    }
+    // Ideographic space, three bytes long, before an element at a depth of 3
+    events.insert(3, XmlEvent::Whitespace("\n\u{3000}".to_string()));
+    for name in names.into_iter().rev() {
+        events.push(XmlEvent::EndElement {
+            name: OwnedName::local(name),
+        });
+    }
+
+    let out = XFlate::new(XFlateConfig::default())
+        .compress_events(events)
+        .unwrap();
+
+    assert_eq!(
+        decompress(&XFlateConfig::default(), &out).unwrap(),
+        "<a><b><c>\n\u{3000}<d/></c></b></a>"
+    );
+}
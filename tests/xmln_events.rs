@@ -0,0 +1,42 @@
+use xflate::{
+    XFlateLimits, XmlNDynamicSymbolTable, XmlNDynamicTagTable, XmlNSymbolTable, XmlNTagTable,
+    decode_xmln_events,
+};
+
+fn tables() -> (XmlNDynamicSymbolTable, XmlNDynamicTagTable) {
+    let mut sym_table = XmlNDynamicSymbolTable::new(2);
+    sym_table.encode('a');
+    let mut tag_table = XmlNDynamicTagTable::default();
+    tag_table.encode("root");
+
+    (sym_table, tag_table)
+}
+
+#[test]
+fn decodes_text() {
+    let (sym_table, tag_table) = tables();
+    let events = decode_xmln_events(
+        "T0 0101 0".to_string(),
+        sym_table,
+        tag_table,
+        &XFlateLimits::default(),
+    )
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap();
+
+    assert!(matches!(&events[2], xml::reader::XmlEvent::Characters(text) if text == "aa"));
+}
+
+#[test]
+fn partial_symbol_code_is_an_error() {
+    let (sym_table, tag_table) = tables();
+    let events = decode_xmln_events(
+        "T0 010 0".to_string(),
+        sym_table,
+        tag_table,
+        &XFlateLimits::default(),
+    )
+    .collect::<Vec<_>>();
+
+    assert!(events.iter().any(Result::is_err));
+}